@@ -0,0 +1,135 @@
+//! Directories of test data from problem packages, e.g. `01.in`, `01.ans`, ..., read as Check
+//! config which includes every pair of input and answer files with `@glob:`.
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// How input and answer files in test data directory are named
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+pub enum DataLayout {
+    /// Detect from files in directory
+    #[default]
+    Auto,
+    /// `1.in` and `1.out`
+    InOut,
+    /// `1.in` and `1.ans`
+    InAns,
+    /// `input/1.txt` and `output/1.txt`, or `input/input1.txt` and `output/output1.txt`
+    Folders,
+}
+
+impl DataLayout {
+    /// Check config with test data in `dir`, paths in it are relative to `dir`
+    pub fn check_config(self, dir: &Path) -> Result<String, String> {
+        let layout = match self {
+            Self::Auto => detect_layout(dir)?,
+            layout => layout,
+        };
+        let (input, output) = match layout {
+            Self::InOut => ("*.in", "*.out"),
+            Self::InAns => ("*.in", "*.ans"),
+            Self::Folders => {
+                let names = file_names(&dir.join("input"))?;
+                if names
+                    .iter()
+                    .any(|name| dir.join("output").join(name).is_file())
+                {
+                    ("input/*", "output/*")
+                } else {
+                    ("input/input*", "output/output*")
+                }
+            }
+            Self::Auto => unreachable!(),
+        };
+        Ok(format!(
+            "----\n@glob:{}\n----\n@glob:{}\n----\n",
+            input, output
+        ))
+    }
+}
+
+fn detect_layout(dir: &Path) -> Result<DataLayout, String> {
+    if dir.join("input").is_dir() && dir.join("output").is_dir() {
+        return Ok(DataLayout::Folders);
+    }
+    let names = file_names(dir)?;
+    let has = |extension: &str| names.iter().any(|name| name.ends_with(extension));
+    if !has(".in") {
+        return Err(format!(
+            "Cannot find test data in {}, expecting .in files or input and output folders",
+            dir.to_str().unwrap()
+        ));
+    }
+    if has(".ans") && !has(".out") {
+        Ok(DataLayout::InAns)
+    } else {
+        Ok(DataLayout::InOut)
+    }
+}
+
+fn file_names(dir: &Path) -> Result<Vec<String>, String> {
+    fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read directory {}: {}", dir.to_str().unwrap(), e))?
+        .map(|entry| {
+            entry
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .map_err(|e| format!("Cannot read directory entry: {}", e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_lazy, GlobLimit, ParseError};
+    use crate::{job::Job, pal::PalType};
+
+    fn names(dir: &str, layout: DataLayout) -> Vec<(String, String)> {
+        let dir = Path::new(dir);
+        let config = layout.check_config(dir).unwrap();
+        let (_, jobs) = parse_lazy(&PalType::Check, &config, dir, 0, GlobLimit::UNLIMITED).unwrap();
+        jobs.collect::<Result<Vec<Job>, ParseError>>()
+            .unwrap()
+            .into_iter()
+            .map(|job| {
+                (
+                    job.name.unwrap(),
+                    String::from_utf8(job.expected_output).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_data_layout() {
+        let expected = |names: [&str; 3]| {
+            names
+                .into_iter()
+                .zip(["3\n", "5\n", "21\n"])
+                .map(|(name, output)| (name.to_string(), output.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names("tests/datadir/in_ans", DataLayout::Auto),
+            expected(["1.in", "2.in", "10.in"])
+        );
+        assert_eq!(
+            names("tests/datadir/folders", DataLayout::Auto),
+            expected(["input/input1.txt", "input/input2.txt", "input/input10.txt"])
+        );
+        assert!(DataLayout::InOut
+            .check_config(Path::new("tests/datadir/in_ans"))
+            .is_ok_and(|config| parse_lazy(
+                &PalType::Check,
+                &config,
+                Path::new("tests/datadir/in_ans"),
+                0,
+                GlobLimit::UNLIMITED
+            )
+            .is_err()));
+        assert!(DataLayout::Auto
+            .check_config(Path::new("tests/datadir"))
+            .is_err());
+    }
+}
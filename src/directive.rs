@@ -0,0 +1,896 @@
+//! Structured generators in test config, written as `{...}` in glob and random inputs:
+//!
+//! - `{n=[1-100]}`: choose a value for variable `n`, and output it
+//! - `{n}`: output value of variable `n`
+//! - `{repeat n: [1-1000]}`, `{lines n: [1-1000] [abc]}`: expand the item `n` times,
+//!   separated by space or newline
+//! - `{perm n}`: a permutation of `1..=n`
+//! - `{distinct n: [1-1000]}`: `n` distinct numbers from range
+//! - `{sorted n: [1-1000]}`: `n` numbers from range, in ascending order
+//! - `{string [1-10]: a-z0-9_}`: a string with length from range, over given alphabet
+//! - `{tree n: star, weight [1-100], base 0}`: edges of a tree, one per line. Shape can be
+//!   `random`(default), `path`, `star` or `caterpillar`
+//! - `{graph n m: connected, weight [1-100]}`: edges of a simple graph, optionally
+//!   `connected`, `disconnected` or `dag`
+//!
+//! Counts and range bounds can be numbers, variables, or ranges like `[1-n]`.
+//!
+//! In glob mode, every value of a variable is enumerated, while other directives are expanded
+//! randomly. Braces not forming a directive, i.e. not starting with a keyword followed by space
+//! or colon or defining a variable with a range, are left untouched. Use `\{` to escape a
+//! directive.
+use crate::graph::{random_graph, random_tree, GraphKind, TreeShape};
+use crate::parser::{check_patterns, expand_random_patterns, parse_char_set, OffsetResult};
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+pub type Variables = HashMap<String, i64>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Value {
+    Literal(i64),
+    Variable(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Count {
+    Fixed(Value),
+    Range(Value, Value),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Directive {
+    Define(String, Count),
+    Variable(String),
+    Repeat {
+        count: Count,
+        separator: &'static str,
+        item: String,
+    },
+    Permutation(Count),
+    Distinct(Count, Value, Value),
+    Sorted(Count, Value, Value),
+    Str(Count, Vec<char>),
+    Tree(Count, TreeShape, EdgeFormat),
+    Graph(Count, Count, GraphKind, EdgeFormat),
+}
+
+/// How edges of trees and graphs are printed
+#[derive(Debug, PartialEq, Eq)]
+struct EdgeFormat {
+    weight: Option<(Value, Value)>,
+    /// Index of first node, usually 0 or 1
+    base: i64,
+}
+
+/// Most items directives can generate for an input, counted over nested directives too, so
+/// counts like `{repeat 10000000000: x}` or `{repeat 100000: {repeat 100000: x}}` are errors
+/// instead of exhausting memory
+pub const MAX_COUNT: i64 = 10_000_000;
+
+const KEYWORDS: [&str; 8] = [
+    "repeat", "lines", "perm", "distinct", "sorted", "string", "tree", "graph",
+];
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+fn parse_value(s: &str) -> Result<Value, String> {
+    let s = s.trim();
+    if let Ok(n) = s.parse() {
+        Ok(Value::Literal(n))
+    } else if s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        Ok(Value::Variable(s.to_string()))
+    } else {
+        Err(format!("Invalid value: {}", s))
+    }
+}
+
+fn parse_range(s: &str) -> Result<(Value, Value), String> {
+    static RANGE_RE: OnceLock<Regex> = OnceLock::new();
+    let cap = regex(&RANGE_RE, r"^\[\s*(-?\w+)\s*-\s*(-?\w+)\s*\]$")
+        .captures(s.trim())
+        .ok_or_else(|| format!("Invalid range: {}", s))?;
+    Ok((parse_value(&cap[1])?, parse_value(&cap[2])?))
+}
+
+fn parse_count(s: &str) -> Result<Count, String> {
+    let s = s.trim();
+    if s.starts_with('[') {
+        let (begin, end) = parse_range(s)?;
+        Ok(Count::Range(begin, end))
+    } else {
+        Ok(Count::Fixed(parse_value(s)?))
+    }
+}
+
+/// Parse count of items generated by directive, rejecting literals above `MAX_COUNT`
+fn parse_item_count(s: &str) -> Result<Count, String> {
+    let count = parse_count(s)?;
+    let literal = match &count {
+        Count::Fixed(Value::Literal(n)) | Count::Range(_, Value::Literal(n)) => Some(*n),
+        _ => None,
+    };
+    match literal {
+        Some(n) if n > MAX_COUNT => Err(count_error(n)),
+        _ => Ok(count),
+    }
+}
+
+fn count_error(n: i64) -> String {
+    format!("Count should be at most {}: {}", MAX_COUNT, n)
+}
+
+fn size_error() -> String {
+    format!("Directives generate more than {} items", MAX_COUNT)
+}
+
+/// Parse comma separated options of `tree` and `graph`
+fn parse_edge_options(
+    argument: Option<&str>,
+    shape: &mut TreeShape,
+    kind: &mut GraphKind,
+    is_tree: bool,
+) -> Result<EdgeFormat, String> {
+    let mut format = EdgeFormat {
+        weight: None,
+        base: 1,
+    };
+    let options = argument
+        .into_iter()
+        .flat_map(|argument| argument.split(','))
+        .map(|option| option.trim())
+        .filter(|option| !option.is_empty());
+    for option in options {
+        match (option, is_tree) {
+            ("random", true) => *shape = TreeShape::Random,
+            ("path", true) => *shape = TreeShape::Path,
+            ("star", true) => *shape = TreeShape::Star,
+            ("caterpillar", true) => *shape = TreeShape::Caterpillar,
+            ("connected", false) => kind.connected = true,
+            ("disconnected", false) => kind.disconnected = true,
+            ("dag", false) => kind.acyclic = true,
+            _ => {
+                if let Some(range) = option.strip_prefix("weight") {
+                    format.weight = Some(parse_range(range)?);
+                } else if let Some(base) = option.strip_prefix("base") {
+                    format.base = base
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid base: {}", base.trim()))?;
+                } else {
+                    return Err(format!("Unknown option: {}", option));
+                }
+            }
+        }
+    }
+    Ok(format)
+}
+
+/// Parse content between braces. Returns `Ok(None)` if it's not a directive, e.g. braces of
+/// `int main() { x = 1; }`.
+fn parse_directive(content: &str, variables: &Variables) -> Result<Option<Directive>, String> {
+    static DEFINE_RE: OnceLock<Regex> = OnceLock::new();
+    static KEYWORD_RE: OnceLock<Regex> = OnceLock::new();
+    let content = content.trim();
+
+    if let Some(cap) = regex(&DEFINE_RE, r"^([A-Za-z_]\w*)\s*=\s*(\[.*\])$").captures(content) {
+        return Ok(parse_range(&cap[2])
+            .ok()
+            .map(|(begin, end)| Directive::Define(cap[1].to_string(), Count::Range(begin, end))));
+    }
+    if variables.contains_key(content) {
+        return Ok(Some(Directive::Variable(content.to_string())));
+    }
+
+    let keyword = content
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .next()
+        .unwrap_or_default();
+    if !KEYWORDS.contains(&keyword) || keyword.len() == content.len() {
+        return Ok(None);
+    }
+    let cap = regex(&KEYWORD_RE, r"(?s)^(\w+)\s+([^:]+?)\s*(?::\s*(.*))?$")
+        .captures(content)
+        .ok_or_else(|| format!("Invalid directive: {{{}}}", content))?;
+    let argument = cap.get(3).map(|m| m.as_str());
+    if &cap[1] == "graph" {
+        static COUNTS_RE: OnceLock<Regex> = OnceLock::new();
+        let counts: Vec<&str> = regex(&COUNTS_RE, r"\[[^\]]*\]|\S+")
+            .find_iter(&cap[2])
+            .map(|m| m.as_str())
+            .collect();
+        if counts.len() != 2 {
+            return Err(format!(
+                "Expect number of nodes and edges in directive: {{{}}}",
+                content
+            ));
+        }
+        let mut kind = GraphKind::default();
+        let format = parse_edge_options(argument, &mut TreeShape::Random, &mut kind, false)?;
+        return Ok(Some(Directive::Graph(
+            parse_item_count(counts[0])?,
+            parse_item_count(counts[1])?,
+            kind,
+            format,
+        )));
+    }
+    let count = parse_item_count(&cap[2])?;
+    let require_argument =
+        || argument.ok_or_else(|| format!("Missing \":\" in directive: {{{}}}", content));
+
+    let directive = match &cap[1] {
+        "repeat" | "lines" => Directive::Repeat {
+            count,
+            separator: if &cap[1] == "repeat" { " " } else { "\n" },
+            item: require_argument()?.to_string(),
+        },
+        "perm" => match argument {
+            None => Directive::Permutation(count),
+            Some(_) => return Err(format!("Unexpected \":\" in directive: {{{}}}", content)),
+        },
+        "distinct" => {
+            let (begin, end) = parse_range(require_argument()?)?;
+            Directive::Distinct(count, begin, end)
+        }
+        "sorted" => {
+            let (begin, end) = parse_range(require_argument()?)?;
+            Directive::Sorted(count, begin, end)
+        }
+        "string" => Directive::Str(count, parse_char_set(require_argument()?.trim())?),
+        "tree" => {
+            let mut shape = TreeShape::Random;
+            let format = parse_edge_options(argument, &mut shape, &mut GraphKind::default(), true)?;
+            Directive::Tree(count, shape, format)
+        }
+        _ => unreachable!(),
+    };
+    Ok(Some(directive))
+}
+
+/// Find next directive at or after `from`, returns its span(including braces) and content
+fn find_directive(
+    input: &str,
+    from: usize,
+    variables: &Variables,
+) -> OffsetResult<Option<(usize, usize, Directive)>> {
+    let bytes = input.as_bytes();
+    let mut start = from;
+    while let Some(offset) = input[start..].find('{') {
+        let open = start + offset;
+        start = open + 1;
+        if open > 0 && bytes[open - 1] == b'\\' {
+            continue;
+        }
+        // Find matching close brace
+        let mut depth = 0;
+        let mut close = None;
+        for (i, b) in bytes.iter().enumerate().skip(open) {
+            match b {
+                b'{' if i == open || bytes[i - 1] != b'\\' => depth += 1,
+                b'}' if bytes[i - 1] != b'\\' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = match close {
+            Some(close) => close,
+            None => continue,
+        };
+        if let Some(directive) =
+            parse_directive(&input[open + 1..close], variables).map_err(|e| (open, e))?
+        {
+            return Ok(Some((open, close + 1, directive)));
+        }
+    }
+    Ok(None)
+}
+
+impl Directive {
+    /// Most items generated by directive, counting only literal counts
+    fn literal_items(&self) -> i64 {
+        fn most(count: &Count) -> i64 {
+            match count {
+                Count::Fixed(Value::Literal(n)) | Count::Range(_, Value::Literal(n)) => (*n).max(0),
+                _ => 0,
+            }
+        }
+        match self {
+            Directive::Define(..) | Directive::Variable(_) => 0,
+            Directive::Repeat { count, .. }
+            | Directive::Permutation(count)
+            | Directive::Distinct(count, ..)
+            | Directive::Sorted(count, ..)
+            | Directive::Str(count, _)
+            | Directive::Tree(count, ..) => most(count),
+            Directive::Graph(nodes, edges, ..) => most(nodes) + most(edges),
+        }
+    }
+
+    /// Variables used by directive, which should be defined before
+    fn used_variables(&self) -> Vec<&str> {
+        fn count_values(count: &Count) -> Vec<&Value> {
+            match count {
+                Count::Fixed(value) => vec![value],
+                Count::Range(begin, end) => vec![begin, end],
+            }
+        }
+        fn weight_values(format: &EdgeFormat) -> Vec<&Value> {
+            format
+                .weight
+                .iter()
+                .flat_map(|(begin, end)| [begin, end])
+                .collect()
+        }
+        let values = match self {
+            Directive::Define(_, count)
+            | Directive::Repeat { count, .. }
+            | Directive::Permutation(count)
+            | Directive::Str(count, _) => count_values(count),
+            Directive::Distinct(count, begin, end) | Directive::Sorted(count, begin, end) => {
+                [count_values(count), vec![begin, end]].concat()
+            }
+            Directive::Tree(count, _, format) => {
+                [count_values(count), weight_values(format)].concat()
+            }
+            Directive::Graph(nodes, edges, _, format) => [
+                count_values(nodes),
+                count_values(edges),
+                weight_values(format),
+            ]
+            .concat(),
+            Directive::Variable(_) => Vec::new(),
+        };
+        values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Variable(name) => Some(name.as_str()),
+                Value::Literal(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Check syntax of directives and patterns without expanding them, and that variables are
+/// defined before use. Returns byte offset of the invalid construct on error.
+pub fn check_directives(input: &str, variables: &mut Variables) -> OffsetResult<()> {
+    check_nested(input, variables, 1, &mut 0)
+}
+
+/// Check directives which are expanded `times` times, adding items of literal counts to `items`
+fn check_nested(
+    input: &str,
+    variables: &mut Variables,
+    times: i64,
+    items: &mut i64,
+) -> OffsetResult<()> {
+    let shift = |offset: usize| move |(o, e): (usize, String)| (offset + o, e);
+    let mut from = 0;
+    while let Some((begin, end, directive)) = find_directive(input, from, variables)? {
+        check_patterns(&input[from..begin]).map_err(shift(from))?;
+        if let Some(name) = directive
+            .used_variables()
+            .into_iter()
+            .find(|name| !variables.contains_key(*name))
+        {
+            return Err((begin, format!("Undefined variable: {}", name)));
+        }
+        *items = items.saturating_add(times.saturating_mul(directive.literal_items()));
+        if *items > MAX_COUNT {
+            return Err((begin, size_error()));
+        }
+        match &directive {
+            Directive::Define(name, _) => {
+                variables.insert(name.clone(), 0);
+            }
+            Directive::Repeat { item, .. } => {
+                // Item is at the end of directive content
+                let content = &input[begin + 1..end - 1];
+                let item_offset = begin + 1 + content.trim_end().len() - item.len();
+                let times = times.saturating_mul(directive.literal_items().max(1));
+                check_nested(item, &mut variables.clone(), times, items)
+                    .map_err(shift(item_offset))?;
+            }
+            _ => {}
+        }
+        from = end;
+    }
+    check_patterns(&input[from..]).map_err(shift(from))
+}
+
+fn resolve(value: &Value, variables: &Variables) -> Result<i64, String> {
+    match value {
+        Value::Literal(n) => Ok(*n),
+        Value::Variable(name) => variables
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("Undefined variable: {}", name)),
+    }
+}
+
+fn resolve_range(begin: &Value, end: &Value, variables: &Variables) -> Result<(i64, i64), String> {
+    let (begin, end) = (resolve(begin, variables)?, resolve(end, variables)?);
+    if begin > end {
+        return Err(format!("Invalid range: [{}-{}]", begin, end));
+    }
+    Ok((begin, end))
+}
+
+fn choose_value<R: Rng>(count: &Count, variables: &Variables, rng: &mut R) -> Result<i64, String> {
+    match count {
+        Count::Fixed(value) => resolve(value, variables),
+        Count::Range(begin, end) => {
+            let (begin, end) = resolve_range(begin, end, variables)?;
+            Ok(rng.gen_range(begin..=end))
+        }
+    }
+}
+
+/// Choose count of items, taking them from `items_left` of the input
+fn choose_count<R: Rng>(
+    count: &Count,
+    variables: &Variables,
+    rng: &mut R,
+    items_left: &mut i64,
+) -> Result<usize, String> {
+    let n = choose_value(count, variables, rng)?;
+    if n > MAX_COUNT {
+        return Err(count_error(n));
+    }
+    let n = usize::try_from(n).map_err(|_| format!("Count should not be negative: {}", n))?;
+    if n as i64 > *items_left {
+        return Err(size_error());
+    }
+    *items_left -= n as i64;
+    Ok(n)
+}
+
+fn join<T: ToString>(values: impl Iterator<Item = T>, separator: &str) -> String {
+    values
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+fn distinct_values<R: Rng>(
+    n: usize,
+    begin: i64,
+    end: i64,
+    rng: &mut R,
+) -> Result<Vec<i64>, String> {
+    let size = (end as i128 - begin as i128 + 1) as u128;
+    if n as u128 > size {
+        return Err(format!(
+            "Cannot choose {} distinct values from [{}-{}]",
+            n, begin, end
+        ));
+    }
+    if size <= (n as u128) * 4 {
+        // Dense: sample indices directly
+        Ok(index::sample(rng, size as usize, n)
+            .into_iter()
+            .map(|i| begin + i as i64)
+            .collect())
+    } else {
+        let mut chosen = HashSet::with_capacity(n);
+        let mut values = Vec::with_capacity(n);
+        while values.len() < n {
+            let v = rng.gen_range(begin..=end);
+            if chosen.insert(v) {
+                values.push(v);
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn format_edges<R: Rng>(
+    edges: Vec<(usize, usize)>,
+    format: &EdgeFormat,
+    variables: &Variables,
+    rng: &mut R,
+) -> Result<String, String> {
+    let weight = match &format.weight {
+        Some((begin, end)) => Some(resolve_range(begin, end, variables)?),
+        None => None,
+    };
+    let lines: Vec<String> = edges
+        .into_iter()
+        .map(|(u, v)| {
+            let (u, v) = (u as i64 + format.base, v as i64 + format.base);
+            match weight {
+                Some((begin, end)) => format!("{} {} {}", u, v, rng.gen_range(begin..=end)),
+                None => format!("{} {}", u, v),
+            }
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn generate<R: Rng>(
+    directive: &Directive,
+    variables: &mut Variables,
+    rng: &mut R,
+    items_left: &mut i64,
+) -> Result<String, String> {
+    match directive {
+        Directive::Define(name, count) => {
+            let value = choose_value(count, variables, rng)?;
+            variables.insert(name.clone(), value);
+            Ok(value.to_string())
+        }
+        Directive::Variable(name) => {
+            Ok(resolve(&Value::Variable(name.clone()), variables)?.to_string())
+        }
+        Directive::Repeat {
+            count,
+            separator,
+            item,
+        } => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            let mut items = Vec::with_capacity(n);
+            for _ in 0..n {
+                let expanded = expand_in(item.clone(), variables.clone(), rng, items_left)?;
+                items.push(expand_random_patterns(expanded, rng)?);
+            }
+            Ok(items.join(separator))
+        }
+        Directive::Permutation(count) => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            let mut values: Vec<usize> = (1..=n).collect();
+            values.shuffle(rng);
+            Ok(join(values.iter(), " "))
+        }
+        Directive::Distinct(count, begin, end) => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            let (begin, end) = resolve_range(begin, end, variables)?;
+            Ok(join(distinct_values(n, begin, end, rng)?.iter(), " "))
+        }
+        Directive::Sorted(count, begin, end) => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            let (begin, end) = resolve_range(begin, end, variables)?;
+            let mut values: Vec<i64> = (0..n).map(|_| rng.gen_range(begin..=end)).collect();
+            values.sort();
+            Ok(join(values.iter(), " "))
+        }
+        Directive::Str(count, alphabet) => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            Ok((0..n).map(|_| *alphabet.choose(rng).unwrap()).collect())
+        }
+        Directive::Tree(count, shape, format) => {
+            let n = choose_count(count, variables, rng, items_left)?;
+            let edges = random_tree(n, *shape, rng);
+            format_edges(edges, format, variables, rng)
+        }
+        Directive::Graph(nodes, edges, kind, format) => {
+            let n = choose_count(nodes, variables, rng, items_left)?;
+            let m = choose_count(edges, variables, rng, items_left)?;
+            let edges = random_graph(n, m, *kind, rng)?;
+            format_edges(edges, format, variables, rng)
+        }
+    }
+}
+
+/// Expand all directives from left to right, with values of variables chosen randomly
+pub fn expand_directives<R: Rng>(
+    input: String,
+    variables: Variables,
+    rng: &mut R,
+) -> Result<String, String> {
+    let mut items_left = MAX_COUNT;
+    expand_in(input, variables, rng, &mut items_left)
+}
+
+/// Expand directives generating at most `items_left` items
+fn expand_in<R: Rng>(
+    mut input: String,
+    mut variables: Variables,
+    rng: &mut R,
+    items_left: &mut i64,
+) -> Result<String, String> {
+    let mut from = 0;
+    while let Some((begin, end, directive)) =
+        find_directive(&input, from, &variables).map_err(|(_, e)| e)?
+    {
+        let generated = generate(&directive, &mut variables, rng, items_left)?;
+        input.replace_range(begin..end, &generated);
+        from = begin + generated.len();
+    }
+    Ok(input)
+}
+
+/// Span of every directive in input
+pub(crate) fn directive_spans(
+    input: &str,
+    variables: &Variables,
+) -> OffsetResult<Vec<(usize, usize)>> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some((begin, end, _)) = find_directive(input, from, variables)? {
+        spans.push((begin, end));
+        from = end;
+    }
+    Ok(spans)
+}
+
+/// A variable whose values are being enumerated
+struct Definition {
+    input: String,
+    variables: Variables,
+    span: (usize, usize),
+    name: String,
+    next: i128,
+    last: i64,
+}
+
+/// Every value of variables defined in input(glob mode), enumerated lazily. Definitions are
+/// replaced by their values, other directives are left to `expand_directives`.
+pub struct Variants {
+    start: Option<String>,
+    definitions: Vec<Definition>,
+}
+
+pub fn enumerate_variables(input: String) -> Variants {
+    Variants {
+        start: Some(input),
+        definitions: Vec::new(),
+    }
+}
+
+impl Iterator for Variants {
+    type Item = Result<(String, Variables), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (input, variables, mut from) = match self.start.take() {
+                Some(input) => (input, Variables::new(), 0),
+                None => {
+                    let definition = self.definitions.last_mut()?;
+                    if definition.next > definition.last as i128 {
+                        self.definitions.pop();
+                        continue;
+                    }
+                    let value = definition.next as i64;
+                    definition.next += 1;
+
+                    let (begin, end) = definition.span;
+                    let value_str = value.to_string();
+                    let mut input = definition.input.clone();
+                    input.replace_range(begin..end, &value_str);
+                    let mut variables = definition.variables.clone();
+                    variables.insert(definition.name.clone(), value);
+                    (input, variables, begin + value_str.len())
+                }
+            };
+
+            // Find next definition to enumerate
+            loop {
+                match find_directive(&input, from, &variables) {
+                    Err((_, e)) => return Some(Err(e)),
+                    Ok(Some((begin, end, Directive::Define(name, count)))) => {
+                        let range = match &count {
+                            Count::Fixed(value) => resolve(value, &variables).map(|v| (v, v)),
+                            Count::Range(begin, end) => resolve_range(begin, end, &variables),
+                        };
+                        let (first, last) = match range {
+                            Ok(range) => range,
+                            Err(e) => return Some(Err(e)),
+                        };
+                        self.definitions.push(Definition {
+                            input,
+                            variables,
+                            span: (begin, end),
+                            name,
+                            next: first as i128,
+                            last,
+                        });
+                        break;
+                    }
+                    Ok(Some((_, end, _))) => from = end,
+                    Ok(None) => return Some(Ok((input, variables))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn expand(input: &str) -> String {
+        expand_directives(
+            input.to_string(),
+            Variables::new(),
+            &mut ChaCha8Rng::seed_from_u64(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_define_and_repeat() {
+        let output = expand("{n=[1-100]}\n{repeat n: [1-9]}\n");
+        let lines: Vec<&str> = output.lines().collect();
+        let n: usize = lines[0].parse().unwrap();
+        let items: Vec<usize> = lines[1]
+            .split(' ')
+            .map(|item| item.parse().unwrap())
+            .collect();
+        assert_eq!(items.len(), n);
+        assert!(items.iter().all(|item| (1..=9).contains(item)));
+    }
+
+    #[test]
+    fn test_lines_with_nested_directive() {
+        let output = expand("{lines 3: {string [2-2]: a-c} {n=[5-5]}}");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for line in lines {
+            let (s, n) = line.split_once(' ').unwrap();
+            assert_eq!(s.len(), 2);
+            assert!(s.chars().all(|c| ('a'..='c').contains(&c)));
+            assert_eq!(n, "5");
+        }
+    }
+
+    #[test]
+    fn test_perm_distinct_sorted() {
+        let output = expand("{n=[10-10]}|{perm n}|{distinct n: [1-10]}|{sorted 5: [-5-5]}");
+        let parts: Vec<Vec<i64>> = output
+            .split('|')
+            .map(|p| p.split(' ').map(|x| x.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(parts[0], vec![10]);
+        let mut perm = parts[1].clone();
+        perm.sort();
+        assert_eq!(perm, (1..=10).collect::<Vec<i64>>());
+        let mut distinct = parts[2].clone();
+        distinct.sort();
+        assert_eq!(distinct, (1..=10).collect::<Vec<i64>>());
+        assert_eq!(parts[3].len(), 5);
+        assert!(parts[3].windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_not_directive() {
+        assert_eq!(expand(r#"{"a": 1} \{n=[1-2]}"#), r#"{"a": 1} \{n=[1-2]}"#);
+        // Braces are directives only with a whole keyword or a definition of valid range
+        for literal in [
+            "{treehouse}",
+            "{perm}",
+            "int main() { x = 1; }",
+            "{n = 5}",
+            "{n=[1-2-3]}",
+        ] {
+            assert_eq!(expand(literal), literal);
+            assert!(check_directives(literal, &mut Variables::new()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_invalid_directive() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        assert!(
+            expand_directives(String::from("{repeat m: 1}"), Variables::new(), &mut rng).is_err()
+        );
+        assert!(expand_directives(String::from("{perm: 3}"), Variables::new(), &mut rng).is_err());
+        assert!(expand_directives(
+            String::from("{distinct 3: [1-2]}"),
+            Variables::new(),
+            &mut rng
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_count_limit() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut expand =
+            |input: &str| expand_directives(input.to_string(), Variables::new(), &mut rng);
+        assert!(expand("{repeat 10000001: x}").is_err());
+        assert!(expand("{graph 10 [1-10000000000]}").is_err());
+        assert!(expand("{n=[10000000000-10000000000]} {perm n}")
+            .unwrap_err()
+            .contains("at most"));
+
+        assert_eq!(
+            check_directives("a {lines 100000000000: x}", &mut Variables::new()),
+            Err((
+                2,
+                String::from("Count should be at most 10000000: 100000000000")
+            ))
+        );
+        // Values of variables are not counts
+        assert!(check_directives("{n=[1-100000000000]}", &mut Variables::new()).is_ok());
+    }
+
+    #[test]
+    fn test_nested_count_limit() {
+        let check = |input: &str| check_directives(input, &mut Variables::new());
+        let too_large = |offset| {
+            Err((
+                offset,
+                String::from("Directives generate more than 10000000 items"),
+            ))
+        };
+        assert_eq!(
+            check("{repeat 10000000: {repeat 10000000: x}}"),
+            too_large(18)
+        );
+        assert_eq!(
+            check("{repeat 6000000: x} {string 6000000: a}"),
+            too_large(20)
+        );
+        assert!(check("{repeat 1000: {lines 1000: {perm 5}}}").is_ok());
+
+        // Counts of variables are only known when expanding, items of nested directives are
+        // taken from the same budget
+        let input = "{n=[30-30]} {repeat n: {repeat n: x}}";
+        assert!(check(input).is_ok());
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut expand = |items_left: i64| {
+            expand_in(input.to_string(), Variables::new(), &mut rng, &mut {
+                items_left
+            })
+        };
+        assert!(expand(930).is_ok());
+        assert_eq!(expand(929), Err(size_error()));
+    }
+
+    #[test]
+    fn test_tree_and_graph() {
+        let output = expand("{n=[5-5]}\n{tree n: star, weight [7-7], base 0}");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5);
+        for line in &lines[1..] {
+            let edge: Vec<i64> = line.split(' ').map(|x| x.parse().unwrap()).collect();
+            assert!(edge[..2].iter().all(|x| (0..5).contains(x)));
+            assert_eq!(edge[2], 7);
+        }
+
+        let output = expand("{graph 4 [6-6]: connected}");
+        assert_eq!(output.lines().count(), 6);
+        assert!(output
+            .split_whitespace()
+            .all(|x| (1..=4).contains(&x.parse().unwrap())));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        assert!(
+            expand_directives(String::from("{graph 4: dag}"), Variables::new(), &mut rng).is_err()
+        );
+        assert!(
+            expand_directives(String::from("{tree 4: dag}"), Variables::new(), &mut rng).is_err()
+        );
+    }
+
+    #[test]
+    fn test_enumerate_variables() {
+        let variants: Vec<(String, Variables)> =
+            enumerate_variables(String::from("{n=[1-2]} {m=[n-2]} {repeat m: x}"))
+                .collect::<Result<_, _>>()
+                .unwrap();
+        let inputs: Vec<&str> = variants.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(
+            inputs,
+            vec![
+                "1 1 {repeat m: x}",
+                "1 2 {repeat m: x}",
+                "2 2 {repeat m: x}"
+            ]
+        );
+        assert_eq!(variants[1].1.get("m"), Some(&2));
+    }
+}
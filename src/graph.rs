@@ -0,0 +1,264 @@
+//! Random trees and graphs for `{tree ...}` and `{graph ...}` directives.
+//! Nodes are `0..n`, edges are returned in random order with random labels.
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeShape {
+    Random,
+    Path,
+    Star,
+    Caterpillar,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphKind {
+    pub connected: bool,
+    pub disconnected: bool,
+    /// Edges `(u, v)` are directed from `u` to `v`, and there is no cycle
+    pub acyclic: bool,
+}
+
+/// Edges of a tree with `n` nodes, edge `(u, v)` has `u` as parent of `v` before relabeling
+pub fn random_tree<R: Rng>(n: usize, shape: TreeShape, rng: &mut R) -> Vec<(usize, usize)> {
+    let edges = tree_edges(n, shape, rng);
+    relabel(n, edges, false, rng)
+}
+
+fn tree_edges<R: Rng>(n: usize, shape: TreeShape, rng: &mut R) -> Vec<(usize, usize)> {
+    match shape {
+        TreeShape::Random => (1..n).map(|v| (below(v, rng), v)).collect(),
+        TreeShape::Path => (1..n).map(|v| (v - 1, v)).collect(),
+        TreeShape::Star => (1..n).map(|v| (0, v)).collect(),
+        TreeShape::Caterpillar => {
+            if n == 0 {
+                return Vec::new();
+            }
+            let spine = below(n, rng) + 1;
+            (1..n)
+                .map(|v| {
+                    if v < spine {
+                        (v - 1, v)
+                    } else {
+                        (below(spine, rng), v)
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+/// Random node below `n`, chosen as `u64` so the same seed gives the same graph on 32 and 64-bit
+/// platforms
+fn below<R: Rng>(n: usize, rng: &mut R) -> usize {
+    rng.gen_range(0..n as u64) as usize
+}
+
+/// Edges of a simple graph(no self loops or multiple edges) with `n` nodes and `m` edges
+pub fn random_graph<R: Rng>(
+    n: usize,
+    m: usize,
+    kind: GraphKind,
+    rng: &mut R,
+) -> Result<Vec<(usize, usize)>, String> {
+    let pairs = |k: usize| (k as u128) * (k.saturating_sub(1) as u128) / 2;
+    if kind.connected && kind.disconnected {
+        return Err(String::from(
+            "Graph cannot be both connected and disconnected",
+        ));
+    }
+
+    let mut edges = Vec::with_capacity(m);
+    let mut chosen = HashSet::with_capacity(m);
+    // Nodes in `0..split` and `split..n` are never joined, for disconnected graph
+    let split = if kind.disconnected {
+        if n < 2 || (m as u128) > pairs(n - 1) {
+            return Err(format!(
+                "Cannot build disconnected graph with {} nodes and {} edges",
+                n, m
+            ));
+        }
+        let valid: Vec<usize> = (1..n)
+            .filter(|&k| pairs(k) + pairs(n - k) >= m as u128)
+            .collect();
+        *valid.choose(rng).unwrap()
+    } else {
+        if (m as u128) > pairs(n) {
+            return Err(format!(
+                "Cannot build simple graph with {} nodes and {} edges",
+                n, m
+            ));
+        }
+        if kind.connected && n > 0 && m < n - 1 {
+            return Err(format!(
+                "Cannot build connected graph with {} nodes and {} edges",
+                n, m
+            ));
+        }
+        n
+    };
+    let allowed = |u: usize, v: usize| (u < split) == (v < split);
+    let total = if split == n {
+        pairs(n)
+    } else {
+        pairs(split) + pairs(n - split)
+    };
+
+    if kind.connected {
+        for edge in tree_edges(n, TreeShape::Random, rng) {
+            chosen.insert(edge);
+            edges.push(edge);
+        }
+    }
+
+    let remaining = m - edges.len();
+    if (chosen.len() + remaining) as u128 * 2 <= total {
+        // Sparse: pick random pairs until enough distinct ones are found
+        while edges.len() < m {
+            let (u, v) = (below(n, rng), below(n, rng));
+            let edge = (u.min(v), u.max(v));
+            if u != v && allowed(u, v) && chosen.insert(edge) {
+                edges.push(edge);
+            }
+        }
+    } else {
+        // Dense: enumerate all remaining pairs, which are at most about 2 * m
+        let candidates: Vec<(usize, usize)> = (0..n)
+            .flat_map(|u| (u + 1..n).map(move |v| (u, v)))
+            .filter(|&(u, v)| allowed(u, v) && !chosen.contains(&(u, v)))
+            .collect();
+        index::sample(rng, candidates.len(), remaining)
+            .into_iter()
+            .for_each(|i| edges.push(candidates[i]));
+    }
+
+    Ok(relabel(n, edges, kind.acyclic, rng))
+}
+
+/// Shuffle node labels and edge order. Edges `(u, v)` with `u < v` keep their direction if
+/// `directed`, so a graph directed along node order stays acyclic.
+fn relabel<R: Rng>(
+    n: usize,
+    mut edges: Vec<(usize, usize)>,
+    directed: bool,
+    rng: &mut R,
+) -> Vec<(usize, usize)> {
+    let mut label: Vec<usize> = (0..n).collect();
+    label.shuffle(rng);
+    edges.shuffle(rng);
+    edges
+        .into_iter()
+        .map(|(u, v)| {
+            if directed || rng.gen() {
+                (label[u], label[v])
+            } else {
+                (label[v], label[u])
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn components(n: usize, edges: &[(usize, usize)]) -> usize {
+        let mut parent: Vec<usize> = (0..n).collect();
+        fn find(parent: &mut Vec<usize>, x: usize) -> usize {
+            if parent[x] != x {
+                parent[x] = find(parent, parent[x]);
+            }
+            parent[x]
+        }
+        for &(u, v) in edges {
+            let (u, v) = (find(&mut parent, u), find(&mut parent, v));
+            parent[u] = v;
+        }
+        (0..n).filter(|&x| find(&mut parent, x) == x).count()
+    }
+
+    fn is_simple(edges: &[(usize, usize)]) -> bool {
+        let set: HashSet<(usize, usize)> =
+            edges.iter().map(|&(u, v)| (u.min(v), u.max(v))).collect();
+        set.len() == edges.len() && edges.iter().all(|(u, v)| u != v)
+    }
+
+    #[test]
+    fn test_random_tree() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for shape in [
+            TreeShape::Random,
+            TreeShape::Path,
+            TreeShape::Star,
+            TreeShape::Caterpillar,
+        ] {
+            let edges = random_tree(50, shape, &mut rng);
+            assert_eq!(edges.len(), 49);
+            assert_eq!(components(50, &edges), 1);
+        }
+        assert!(random_tree(1, TreeShape::Random, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_random_graph() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let connected = GraphKind {
+            connected: true,
+            ..Default::default()
+        };
+        for m in [9, 20, 45] {
+            let edges = random_graph(10, m, connected, &mut rng).unwrap();
+            assert_eq!(edges.len(), m);
+            assert!(is_simple(&edges));
+            assert_eq!(components(10, &edges), 1);
+        }
+        assert!(random_graph(10, 8, connected, &mut rng).is_err());
+        assert!(random_graph(10, 46, GraphKind::default(), &mut rng).is_err());
+
+        let disconnected = GraphKind {
+            disconnected: true,
+            ..Default::default()
+        };
+        let edges = random_graph(10, 36, disconnected, &mut rng).unwrap();
+        assert!(is_simple(&edges));
+        assert!(components(10, &edges) > 1);
+        assert!(random_graph(10, 37, disconnected, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_random_dag() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let dag = GraphKind {
+            acyclic: true,
+            ..Default::default()
+        };
+        let n = 20;
+        let edges = random_graph(n, 100, dag, &mut rng).unwrap();
+        // Kahn's algorithm visits every node iff there is no cycle
+        let mut in_degree = vec![0; n];
+        edges.iter().for_each(|&(_, v)| in_degree[v] += 1);
+        let mut queue: Vec<usize> = (0..n).filter(|&x| in_degree[x] == 0).collect();
+        let mut visited = 0;
+        while let Some(u) = queue.pop() {
+            visited += 1;
+            for &(_, v) in edges.iter().filter(|&&(from, _)| from == u) {
+                in_degree[v] -= 1;
+                if in_degree[v] == 0 {
+                    queue.push(v);
+                }
+            }
+        }
+        assert_eq!(visited, n);
+    }
+
+    #[test]
+    fn test_seeded_tree() {
+        // Inputs are reproduced from seeds in stores, so a seed must give the same tree
+        // everywhere
+        let edges = random_tree(5, TreeShape::Random, &mut ChaCha8Rng::seed_from_u64(0));
+        assert_eq!(edges, vec![(1, 0), (3, 1), (4, 0), (4, 2)]);
+    }
+}
@@ -1,26 +1,38 @@
+use crate::limits::{format_memory, format_time};
 use crate::pal::{PalInfo, PalType};
+use crate::sandbox::sandbox;
+use crate::workdir::JobDir;
 use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Write;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 use wait_timeout::ChildExt;
 
-#[derive(PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ChildError {
     TimeOut(u64),
     SpawnError(String),
     InputOutputError(String),
     InvalidExitCode(Option<i32>),
+    /// Killed by SIGSYS, for a system call forbidden by sandbox
+    SecurityViolation,
 }
 
 impl std::fmt::Debug for ChildError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             Self::TimeOut(timeout) => {
-                write!(f, "Child process haven't exited for {} secs", timeout)
+                write!(f, "Child process haven't exited for {}ms", timeout)
             }
             Self::SpawnError(e) => {
                 write!(f, "Failed to spawn child process: {}", e)
@@ -35,26 +47,118 @@ impl std::fmt::Debug for ChildError {
                     write!(f, "Child returned: {}", code.unwrap())
                 }
             }
+            Self::SecurityViolation => {
+                write!(f, "Child killed for forbidden system call")
+            }
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Job {
     pub id: usize,
     pub input: Vec<u8>,
     pub expected_output: Vec<u8>,
     pub actual_output: Vec<u8>,
+    /// Hash of the std program which produced `expected_output`, None if it's from test config
+    #[serde(default)]
+    pub std_hash: Option<String>,
+    /// Seed used to generate `input`, passed to generator program or used for random expansion
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Name shown in reports instead of id, e.g. file the input is read from
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Group of tests the job is in, results are reported for each group
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Time limit of tested program in milliseconds, instead of the one of all tests
+    #[serde(default)]
+    pub time_limit: Option<u64>,
+    /// Memory limit of tested program in bytes
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    /// Time tested program took in milliseconds
+    #[serde(default)]
+    pub time: Option<u64>,
+    /// Command-line arguments passed to tested(and std) program
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables set for tested(and std) program
+    #[serde(default)]
+    pub env: Vec<(String, String)>,
+}
+impl Job {
+    /// Id of job, followed by its group and name if any
+    pub fn label(&self) -> String {
+        match self.full_name() {
+            Some(name) => format!("{}({})", self.id, name),
+            None => self.id.to_string(),
+        }
+    }
+
+    /// Time tested program took and its time limit, like `35ms/1s`, and memory limit if any
+    pub fn limits_report(&self, timeout_ms: u64) -> String {
+        let time = match self.time {
+            Some(time) => format!("{}ms", time),
+            None => String::from("-"),
+        };
+        let report = format!(
+            "{}/{}",
+            time,
+            format_time(self.time_limit.unwrap_or(timeout_ms))
+        );
+        match self.memory_limit {
+            Some(memory) => format!("{}, {}", report, format_memory(memory)),
+            None => report,
+        }
+    }
+
+    /// Group and name of job joined by `/`
+    pub fn full_name(&self) -> Option<String> {
+        match (&self.group, &self.name) {
+            (Some(group), Some(name)) => Some(format!("{}/{}", group, name)),
+            (group, name) => name.clone().or_else(|| group.clone()),
+        }
+    }
+}
+
+/// How output of user program is compared with expected output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparator {
+    /// Same after trailing whitespace is trimmed
+    #[default]
+    Trim,
+    /// Same byte by byte
+    Exact,
+    /// Same sequence of tokens separated by whitespace
+    Tokens,
 }
-#[derive(Deserialize, Serialize)]
+
+/// Files tested(and std) program reads input from and writes output to, instead of stdin and
+/// stdout, like `problem.in` and `problem.out` of older OI problems
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileIo {
+    pub input: String,
+    pub output: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub enum JobResult {
     Success,
     Accepted,
     WrongAnswer,
+    /// Judged by checker with score from 0 to 1
+    PartiallyCorrect(f64),
     TimeLimitExceed,
     RuntimeError,
+    /// Tested program made a system call forbidden by sandbox
+    SecurityViolation,
     OtherError(String),
     StdProgramError(ChildError),
+    GeneratorError(ChildError),
 }
 
 impl std::fmt::Display for JobResult {
@@ -63,37 +167,283 @@ impl std::fmt::Display for JobResult {
             Self::Accepted => write!(f, "."),
             Self::Success => write!(f, ""),
             Self::WrongAnswer => write!(f, "WA"),
+            Self::PartiallyCorrect(score) => write!(f, "PC({})", score),
             Self::TimeLimitExceed => write!(f, "TLE"),
             Self::RuntimeError => write!(f, "REG"),
+            Self::SecurityViolation => write!(f, "SV"),
             Self::OtherError(s) => write!(f, "OE({})", s),
             Self::StdProgramError(e) => write!(f, "STDERR({:?})", e),
+            Self::GeneratorError(e) => write!(f, "GENERR({:?})", e),
         }
     }
 }
 
 impl JobResult {
     pub fn is_passed(&self) -> bool {
-        match &self {
-            Self::Accepted => true,
-            Self::Success => true,
-            _ => false,
+        matches!(&self, Self::Accepted | Self::Success)
+    }
+
+    /// Score of test from 0 to 1, partial for partially correct output
+    pub fn score(&self) -> f64 {
+        match self {
+            Self::Accepted | Self::Success => 1.0,
+            Self::PartiallyCorrect(score) => *score,
+            _ => 0.0,
         }
     }
 }
 
 pub fn run_prog(
     prog: &str,
+    args: &[String],
+    env: &[(String, String)],
     work_directory: &str,
-    timeout_sec: u64,
+    timeout_ms: u64,
     input: &[u8],
 ) -> Result<Vec<u8>, ChildError> {
-    let mut out_buffer = Vec::new();
+    let command = prog_command(prog, args, env, work_directory);
+    spawn_and_wait(command, timeout_ms, input, false)
+}
+
+/// Run tested program in `dir` with arguments, environment and limits of job, and record time
+/// it takes in job
+fn run_user_prog(
+    pal_info: &PalInfo,
+    job: &mut Job,
+    dir: &Path,
+    live: bool,
+) -> Result<Vec<u8>, ChildError> {
+    let mut command = job_command(&pal_info.prog, job, dir)?;
+    if let Some(memory) = job.memory_limit {
+        limit_memory(&mut command, memory);
+    }
+    if pal_info.sandbox {
+        sandbox(&mut command, dir).map_err(ChildError::SpawnError)?;
+    }
+    let now = Instant::now();
+    let run_result = run_job_prog(
+        pal_info,
+        command,
+        job,
+        dir,
+        job.time_limit.unwrap_or(pal_info.timeout_ms),
+        live,
+    );
+    job.time = Some(now.elapsed().as_millis() as u64);
+    run_result
+}
+
+/// Run std program with arguments and environment of job, in a working directory of its own
+fn run_std_prog(pal_info: &PalInfo, job: &Job, live: bool) -> Result<Vec<u8>, ChildError> {
+    let dir = JobDir::create(&pal_info.fixtures).map_err(ChildError::InputOutputError)?;
+    let command = job_command(pal_info.std.as_ref().unwrap(), job, dir.path())?;
+    run_job_prog(
+        pal_info,
+        command,
+        job,
+        dir.path(),
+        pal_info
+            .std_limit
+            .of_test(job.time_limit, pal_info.timeout_ms),
+        live,
+    )
+}
+
+/// Command running program in `dir` with arguments and environment of job
+fn job_command(prog: &str, job: &Job, dir: &Path) -> Result<Command, ChildError> {
+    dir_command(prog, &job.args, &job.env, dir)
+}
+
+/// Command running compiled program in `dir`
+fn dir_command(
+    prog: &str,
+    args: &[String],
+    env: &[(String, String)],
+    dir: &Path,
+) -> Result<Command, ChildError> {
+    // Compiled program runs in another directory, so it's found by absolute path
+    let prog = env::current_dir()
+        .map(|dir| dir.join(prog))
+        .map_err(|e| ChildError::SpawnError(e.to_string()))?;
+    Ok(prog_command(
+        prog.to_str().unwrap(),
+        args,
+        env,
+        dir.to_str().unwrap(),
+    ))
+}
+
+/// Run command of program in `dir` with input of job, through stdin and stdout, or through
+/// files if tests use file I/O
+fn run_job_prog(
+    pal_info: &PalInfo,
+    command: Command,
+    job: &Job,
+    dir: &Path,
+    timeout_ms: u64,
+    live: bool,
+) -> Result<Vec<u8>, ChildError> {
+    let file_io = match &pal_info.file_io {
+        Some(file_io) => file_io,
+        None => return spawn_and_wait(command, timeout_ms, &job.input, live),
+    };
+
+    fs::write(dir.join(&file_io.input), &job.input).map_err(|e| {
+        ChildError::InputOutputError(format!("Cannot write input file {}: {}", file_io.input, e))
+    })?;
+    spawn_and_wait(command, timeout_ms, &[], live)?;
+    fs::read(dir.join(&file_io.output)).map_err(|e| {
+        ChildError::InputOutputError(format!("Cannot read output file {}: {}", file_io.output, e))
+    })
+}
+
+/// Run job with `run` in a working directory of its own, which is kept in test info directory
+/// if the job fails
+fn in_job_dir(
+    pal_info: &PalInfo,
+    job: Job,
+    run: impl FnOnce(Job, &Path) -> (Job, JobResult),
+) -> (Job, JobResult) {
+    let dir = match JobDir::create(&pal_info.fixtures) {
+        Ok(dir) => dir,
+        Err(e) => return (job, JobResult::OtherError(e)),
+    };
+    let (job, job_result) = run(job, dir.path());
+    let kept = kept_dir(&pal_info.test_info_directory, job.id);
+    if job_result.is_passed() {
+        // Left by an earlier run where the job failed
+        let _ = fs::remove_dir_all(&kept);
+    } else {
+        // Failed job is still reported if its directory can't be kept
+        let _ = dir.keep(&kept);
+    }
+    (job, job_result)
+}
+
+/// Where working directory of failed job is kept
+pub fn kept_dir(test_info_directory: &str, id: usize) -> PathBuf {
+    Path::new(test_info_directory)
+        .join(id.to_string())
+        .join("work")
+}
+
+fn prog_command(
+    prog: &str,
+    args: &[String],
+    env: &[(String, String)],
+    work_directory: &str,
+) -> Command {
+    let mut command = Command::new(prog);
+    command
+        .args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .current_dir(work_directory);
+    command
+}
+
+/// Number of stray processes killed with timed out programs, see `take_stray_processes`
+static STRAY_PROCESSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of stray processes killed with timed out programs since last call
+pub fn take_stray_processes() -> usize {
+    STRAY_PROCESSES.swap(0, Ordering::Relaxed)
+}
 
-    let mut p = Command::new(prog)
+/// Kill timed out child with processes in its process group, e.g. those it forked, and reap it
+#[cfg(unix)]
+fn kill_process_tree(child: &mut Child) {
+    let pgid = child.id() as libc::pid_t;
+    let stray = process_group_size(pgid).saturating_sub(1);
+    // Safety: kill only sends a signal
+    unsafe {
+        libc::kill(-pgid, libc::SIGKILL);
+    }
+    let _ = child.wait();
+    STRAY_PROCESSES.fetch_add(stray, Ordering::Relaxed);
+}
+
+#[cfg(not(unix))]
+fn kill_process_tree(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Number of processes in process group `pgid`
+#[cfg(target_os = "linux")]
+fn process_group_size(pgid: libc::pid_t) -> usize {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|entry| fs::read_to_string(entry.ok()?.path().join("stat")).ok())
+        .filter(|stat| {
+            // Fields after `(comm)` are state, ppid and pgrp, comm may contain spaces
+            let fields = stat.rsplit_once(')').map(|(_, fields)| fields);
+            fields.and_then(|fields| fields.split_whitespace().nth(2))
+                == Some(pgid.to_string().as_str())
+        })
+        .count()
+}
+
+/// Processes of group can't be listed, only the child is counted
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_group_size(_pgid: libc::pid_t) -> usize {
+    1
+}
+
+/// Limit address space of child to `memory` bytes, so allocating more than it fails
+#[cfg(unix)]
+fn limit_memory(command: &mut Command, memory: u64) {
+    use std::os::unix::process::CommandExt;
+    let limit = libc::rlimit {
+        rlim_cur: memory as libc::rlim_t,
+        rlim_max: memory as libc::rlim_t,
+    };
+    // Safety: only setrlimit, which is async-signal-safe, is called between fork and exec
+    unsafe {
+        command.pre_exec(move || match libc::setrlimit(libc::RLIMIT_AS, &limit) {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        });
+    }
+}
+
+/// Memory can't be limited on this platform
+#[cfg(not(unix))]
+fn limit_memory(_command: &mut Command, _memory: u64) {}
+
+/// Print output read since `printed` bytes of it, with `print!` so it's captured in tests. Unless
+/// output ended, a UTF-8 character split between reads waits for the rest of it
+fn print_live(printed: &mut usize, output: &[u8], ended: bool) -> std::io::Result<()> {
+    let rest = &output[*printed..];
+    let len = match std::str::from_utf8(rest) {
+        Err(e) if !ended && e.error_len().is_none() => e.valid_up_to(),
+        _ => rest.len(),
+    };
+    print!("{}", String::from_utf8_lossy(&rest[..len]));
+    *printed += len;
+    std::io::stdout().flush()
+}
+
+fn spawn_and_wait(
+    mut command: Command,
+    timeout_ms: u64,
+    input: &[u8],
+    live: bool,
+) -> Result<Vec<u8>, ChildError> {
+    // Child leads a process group of its own, so processes it forks can be killed with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .current_dir(work_directory)
+        .stderr(if live {
+            Stdio::inherit()
+        } else {
+            Stdio::piped()
+        });
+    let mut p = command
         .spawn()
         .map_err(|e| ChildError::SpawnError(format!("{:?}", e)))?;
 
@@ -107,157 +457,299 @@ pub fn run_prog(
         .take()
         .ok_or_else(|| ChildError::InputOutputError(String::from("Child stdout is None")))?;
 
-    child_stdin
-        .write(input)
-        .map_err(|e| ChildError::InputOutputError(format!("Cannot write to child stdin: {}", e)))?;
-
-    drop(child_stdin);
-
-    let wait_result = p.wait_timeout(Duration::from_secs(timeout_sec)).unwrap();
+    // Read stdout while child is running, so it won't block on a full pipe
+    let reader = thread::spawn(move || -> std::io::Result<Vec<u8>> {
+        let mut printed = 0;
+        let mut out_buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let n = child_stdout.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            out_buffer.extend_from_slice(&chunk[..n]);
+            if live {
+                print_live(&mut printed, &out_buffer, false)?;
+            }
+        }
+        if live {
+            print_live(&mut printed, &out_buffer, true)?;
+        }
+        Ok(out_buffer)
+    });
 
-    if wait_result.is_none() {
-        return Err(ChildError::TimeOut(timeout_sec));
+    // Child may exit without reading all of its input
+    if let Err(e) = child_stdin.write_all(input) {
+        if e.kind() != ErrorKind::BrokenPipe {
+            // Don't leave child running, or the reader waiting for its output
+            drop(child_stdin);
+            kill_process_tree(&mut p);
+            let _ = reader.join();
+            return Err(ChildError::InputOutputError(format!(
+                "Cannot write to child stdin: {}",
+                e
+            )));
+        }
     }
 
-    let child_status_code = wait_result.unwrap().code();
-
-    if child_status_code.is_none() {
-        return Err(ChildError::InvalidExitCode(None));
-    }
+    drop(child_stdin);
 
-    let child_status_code = child_status_code.unwrap();
+    let wait_result = p.wait_timeout(Duration::from_millis(timeout_ms)).unwrap();
 
-    if child_status_code != 0 {
-        return Err(ChildError::InvalidExitCode(Some(child_status_code)));
-    }
+    let exit_status = match wait_result {
+        Some(exit_status) => exit_status,
+        None => {
+            kill_process_tree(&mut p);
+            return Err(ChildError::TimeOut(timeout_ms));
+        }
+    };
 
-    child_stdout.read_to_end(&mut out_buffer).map_err(|e| {
+    let out_buffer = reader.join().unwrap().map_err(|e| {
         ChildError::InputOutputError(format!("Cannot read from child stdout: {}", e))
     })?;
 
-    Ok(out_buffer)
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if exit_status.signal() == Some(libc::SIGSYS) {
+            return Err(ChildError::SecurityViolation);
+        }
+    }
+    match exit_status.code() {
+        None => Err(ChildError::InvalidExitCode(None)),
+        Some(0) => Ok(out_buffer),
+        Some(code) => Err(ChildError::InvalidExitCode(Some(code))),
+    }
 }
 
 pub fn run_job(pal_type: Arc<PalType>, pal_info: Arc<PalInfo>, job: Job) -> (Job, JobResult) {
-    match *pal_type {
-        PalType::Check => run_job_check(pal_info, job),
-        PalType::Pal => run_job_pal(pal_info, job),
-        PalType::RandomPal => run_job_pal(pal_info, job),
-        PalType::Retest => run_job_check(pal_info, job),
-    }
+    in_job_dir(&pal_info, job, |mut job, dir| {
+        if let (Some(generator), Some(seed)) = (&pal_info.generator, job.seed) {
+            match generate_input(generator, dir, pal_info.timeout_ms, seed) {
+                Ok(input) => job.input = input,
+                Err(e) => return (job, JobResult::GeneratorError(e)),
+            }
+        }
+        match *pal_type {
+            PalType::Check => run_job_check(&pal_info, job, dir),
+            PalType::Pal => run_job_pal(&pal_info, job, dir),
+            PalType::RandomPal => run_job_pal(&pal_info, job, dir),
+            PalType::Retest => run_job_check(&pal_info, job, dir),
+        }
+    })
 }
 
-fn run_job_check(pal_info: Arc<PalInfo>, mut job: Job) -> (Job, JobResult) {
-    let run_result = run_prog(
-        &pal_info.prog,
-        &pal_info.work_directory,
-        pal_info.timeout_sec,
-        &job.input,
-    );
+/// Run generator program in `dir` with seed as its only argument, and use its output as input
+pub fn generate_input(
+    generator: &str,
+    dir: &Path,
+    timeout_ms: u64,
+    seed: u64,
+) -> Result<Vec<u8>, ChildError> {
+    let command = dir_command(generator, &[seed.to_string()], &[], dir)?;
+    spawn_and_wait(command, timeout_ms, &[], false)
+}
 
-    if run_result.is_err() {
-        let run_error = run_result.err().unwrap();
-        match run_error {
-            ChildError::TimeOut(_) => (job, JobResult::TimeLimitExceed),
-            ChildError::InputOutputError(e) => (job, JobResult::OtherError(e)),
-            ChildError::InvalidExitCode(None) => (job, JobResult::RuntimeError),
-            ChildError::InvalidExitCode(Some(_)) => (job, JobResult::RuntimeError),
-            ChildError::SpawnError(e) => (job, JobResult::OtherError(e)),
-        }
-    } else {
-        let output = run_result.unwrap();
-        // for text output, trim before compare
-        // for binary output, just compare
-        job.actual_output = output.clone();
-        match String::from_utf8(job.expected_output.clone()) {
-            Ok(s) => {
-                let expected_output_trim = s.trim_end().as_bytes();
-                job.expected_output = expected_output_trim.to_vec();
-                match String::from_utf8(output) {
-                    Ok(s) => {
-                        let actual_outpupt_trim = s.trim_end().as_bytes();
-                        job.actual_output = actual_outpupt_trim.to_vec();
-                        if expected_output_trim == actual_outpupt_trim {
-                            (job, JobResult::Accepted)
-                        } else {
-                            (job, JobResult::WrongAnswer)
-                        }
-                    }
-                    Err(_) => (job, JobResult::WrongAnswer),
-                }
-            }
-            Err(_) => {
-                if output == job.expected_output {
-                    (job, JobResult::Accepted)
-                } else {
-                    (job, JobResult::WrongAnswer)
-                }
+/// Run a single job with output of user(and std) program shown on terminal
+pub fn run_job_live(pal_type: &PalType, pal_info: &PalInfo, job: Job) -> (Job, JobResult) {
+    in_job_dir(pal_info, job, |job, dir| {
+        run_job_live_in(pal_type, pal_info, job, dir)
+    })
+}
+
+fn run_job_live_in(
+    pal_type: &PalType,
+    pal_info: &PalInfo,
+    mut job: Job,
+    dir: &Path,
+) -> (Job, JobResult) {
+    println!("User program output:");
+    let user_run_result = run_user_prog(pal_info, &mut job, dir, true);
+    println!();
+
+    let user_output = match user_run_result {
+        Err(run_error) => return (job, user_error_result(run_error)),
+        Ok(output) => output,
+    };
+
+    if let PalType::Pal | PalType::RandomPal = pal_type {
+        println!("Std program output:");
+        let std_run_result = run_std_prog(pal_info, &job, true);
+        println!();
+
+        match std_run_result {
+            Err(run_error) => return (job, JobResult::StdProgramError(run_error)),
+            Ok(std_output) => {
+                job.expected_output = std_output;
+                job.std_hash = pal_info.std_hash.clone();
             }
         }
     }
+
+    judge_output(pal_info, job, user_output, dir)
 }
 
-fn run_job_pal(pal_info: Arc<PalInfo>, mut job: Job) -> (Job, JobResult) {
-    let user_run_result = run_prog(
-        &pal_info.prog,
-        &pal_info.work_directory,
-        pal_info.timeout_sec,
-        &job.input,
-    );
+fn run_job_check(pal_info: &PalInfo, mut job: Job, dir: &Path) -> (Job, JobResult) {
+    let run_result = run_user_prog(pal_info, &mut job, dir, false);
+
+    match run_result {
+        Err(run_error) => (job, user_error_result(run_error)),
+        Ok(output) => judge_output(pal_info, job, output, dir),
+    }
+}
 
-    let std_program = pal_info.std.as_ref().unwrap();
+fn run_job_pal(pal_info: &PalInfo, mut job: Job, dir: &Path) -> (Job, JobResult) {
+    let user_run_result = run_user_prog(pal_info, &mut job, dir, false);
 
-    if user_run_result.is_err() {
-        let run_error = user_run_result.err().unwrap();
-        match run_error {
-            ChildError::TimeOut(_) => (job, JobResult::TimeLimitExceed),
-            ChildError::InputOutputError(e) => (job, JobResult::OtherError(e)),
-            ChildError::InvalidExitCode(None) => (job, JobResult::RuntimeError),
-            ChildError::InvalidExitCode(Some(_)) => (job, JobResult::RuntimeError),
-            ChildError::SpawnError(e) => (job, JobResult::OtherError(e)),
+    let user_output = match user_run_result {
+        Err(run_error) => return (job, user_error_result(run_error)),
+        Ok(output) => output,
+    };
+
+    let std_run_result = run_std_prog(pal_info, &job, false);
+    match std_run_result {
+        Err(run_error) => (job, JobResult::StdProgramError(run_error)),
+        Ok(std_output) => {
+            job.expected_output = std_output;
+            job.std_hash = pal_info.std_hash.clone();
+            judge_output(pal_info, job, user_output, dir)
         }
-    } else {
-        let user_output = user_run_result.unwrap();
-        let std_run_result = run_prog(
-            &std_program,
-            &pal_info.work_directory,
-            pal_info.timeout_sec,
-            &job.input,
-        );
-        if std_run_result.is_err() {
-            return (
+    }
+}
+
+fn user_error_result(run_error: ChildError) -> JobResult {
+    match run_error {
+        ChildError::TimeOut(_) => JobResult::TimeLimitExceed,
+        ChildError::InputOutputError(e) => JobResult::OtherError(e),
+        ChildError::InvalidExitCode(None) => JobResult::RuntimeError,
+        ChildError::InvalidExitCode(Some(_)) => JobResult::RuntimeError,
+        ChildError::SecurityViolation => JobResult::SecurityViolation,
+        ChildError::SpawnError(e) => JobResult::OtherError(e),
+    }
+}
+
+/// Judge output of user program with checker if there is one, or compare it with expected output
+fn judge_output(pal_info: &PalInfo, job: Job, output: Vec<u8>, dir: &Path) -> (Job, JobResult) {
+    match &pal_info.checker {
+        Some(checker) => run_checker(checker, pal_info, job, output, dir),
+        None => compare_output(pal_info.comparator, job, output),
+    }
+}
+
+/// Run checker in working directory `dir` of job, with files of input, output and expected
+/// output in a temporary directory, and a result file it may write score to
+fn run_checker(
+    checker: &str,
+    pal_info: &PalInfo,
+    mut job: Job,
+    output: Vec<u8>,
+    dir: &Path,
+) -> (Job, JobResult) {
+    job.actual_output = output;
+    let files_dir = env::temp_dir().join(format!("rpal-checker-{}", Uuid::new_v4()));
+    let files = [
+        ("input", &job.input),
+        ("output", &job.actual_output),
+        ("answer", &job.expected_output),
+    ];
+    let written = fs::create_dir(&files_dir).and_then(|_| {
+        files
+            .iter()
+            .try_for_each(|(name, content)| fs::write(files_dir.join(name), content))
+    });
+    let run_result = written
+        .map_err(|e| ChildError::InputOutputError(format!("Cannot write files for checker: {}", e)))
+        .and_then(|_| {
+            let args: Vec<String> = files
+                .iter()
+                .map(|(name, _)| name)
+                .chain(&["result"])
+                .map(|name| files_dir.join(name).to_str().unwrap().to_string())
+                .collect();
+            let command = dir_command(checker, &args, &[], dir)?;
+            spawn_and_wait(command, pal_info.timeout_ms, &[], false)
+        });
+    let result = fs::read_to_string(files_dir.join("result")).unwrap_or_default();
+    let _ = fs::remove_dir_all(&files_dir);
+
+    match run_result {
+        Ok(_) => (job, JobResult::Accepted),
+        Err(ChildError::InvalidExitCode(Some(1 | 2))) => (job, JobResult::WrongAnswer),
+        // Partially correct as quitp() of testlib
+        Err(ChildError::InvalidExitCode(Some(7))) => match checker_points(&result) {
+            Some(score) => (job, JobResult::PartiallyCorrect(score)),
+            None => (
                 job,
-                JobResult::StdProgramError(std_run_result.err().unwrap()),
-            );
-        }
-        let std_output = std_run_result.unwrap();
-        // for text output, trim before compare
-        // for binary output, just compare
-        job.actual_output = user_output.clone();
-        job.expected_output = std_output;
-        match String::from_utf8(job.expected_output.clone()) {
-            Ok(s) => {
-                let expected_output_trim = s.trim_end().as_bytes();
-                job.expected_output = expected_output_trim.to_vec();
-                match String::from_utf8(user_output) {
-                    Ok(s) => {
-                        let actual_outpupt_trim = s.trim_end().as_bytes();
-                        job.actual_output = actual_outpupt_trim.to_vec();
-                        if expected_output_trim == actual_outpupt_trim {
-                            (job, JobResult::Accepted)
-                        } else {
-                            (job, JobResult::WrongAnswer)
-                        }
+                JobResult::OtherError(format!(
+                    "Checker exited with 7 without points from 0 to 1 in result: {}",
+                    result.trim()
+                )),
+            ),
+        },
+        Err(e) => (
+            job,
+            JobResult::OtherError(format!("Checker failed: {:?}", e)),
+        ),
+    }
+}
+
+/// Score in result written by checker, like `points 0.5 message`
+fn checker_points(result: &str) -> Option<f64> {
+    let mut words = result.split_whitespace();
+    if words.next()? != "points" {
+        return None;
+    }
+    let score: f64 = words.next()?.parse().ok()?;
+    (0.0..=1.0).contains(&score).then_some(score)
+}
+
+fn compare_output(comparator: Comparator, mut job: Job, output: Vec<u8>) -> (Job, JobResult) {
+    let accepted = match comparator {
+        Comparator::Trim => return compare_trimmed(job, output),
+        Comparator::Exact => output == job.expected_output,
+        Comparator::Tokens => tokens(&output) == tokens(&job.expected_output),
+    };
+    job.actual_output = output;
+    if accepted {
+        (job, JobResult::Accepted)
+    } else {
+        (job, JobResult::WrongAnswer)
+    }
+}
+
+fn tokens(output: &[u8]) -> Vec<&[u8]> {
+    output
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+fn compare_trimmed(mut job: Job, output: Vec<u8>) -> (Job, JobResult) {
+    // for text output, trim before compare
+    // for binary output, just compare
+    job.actual_output = output.clone();
+    match String::from_utf8(job.expected_output.clone()) {
+        Ok(s) => {
+            let expected_output_trim = s.trim_end().as_bytes();
+            job.expected_output = expected_output_trim.to_vec();
+            match String::from_utf8(output) {
+                Ok(s) => {
+                    let actual_outpupt_trim = s.trim_end().as_bytes();
+                    job.actual_output = actual_outpupt_trim.to_vec();
+                    if expected_output_trim == actual_outpupt_trim {
+                        (job, JobResult::Accepted)
+                    } else {
+                        (job, JobResult::WrongAnswer)
                     }
-                    Err(_) => (job, JobResult::WrongAnswer),
                 }
+                Err(_) => (job, JobResult::WrongAnswer),
             }
-            Err(_) => {
-                if job.actual_output == job.expected_output {
-                    (job, JobResult::Accepted)
-                } else {
-                    (job, JobResult::WrongAnswer)
-                }
+        }
+        Err(_) => {
+            if output == job.expected_output {
+                (job, JobResult::Accepted)
+            } else {
+                (job, JobResult::WrongAnswer)
             }
         }
     }
@@ -1,9 +1,19 @@
 use crate::pal::PalStore;
-use crate::{job::JobResult, pal::run_retest};
+use crate::{
+    job::{Comparator, FileIo, JobResult},
+    pal::run_retest,
+};
 use clap::{Parser, Subcommand};
+use datadir::DataLayout;
 use directories::ProjectDirs;
 use job::Job;
-use pal::{run_pal, CompileConfig, PalType};
+use limits::{parse_multiplier, parse_time};
+use pal::{
+    print_groups, print_subtasks, run_pal, run_single, run_stress, CompileConfig, PalType,
+    RetestBase, StressConfig,
+};
+use parser::{format_count, parse_generator_source, random_pal_job, GlobLimit, TestConfig};
+use rand::{seq::SliceRandom, thread_rng};
 use session::Session;
 use std::{
     collections::HashMap,
@@ -11,13 +21,22 @@ use std::{
     fs::{self, File},
     io::ErrorKind,
     path::Path,
+    time::Duration,
 };
 
+pub mod datadir;
+pub mod directive;
+pub mod graph;
 pub mod job;
+pub mod limits;
 pub mod pal;
 pub mod parser;
+pub mod problem;
+pub mod sandbox;
 pub mod session;
 pub mod threadpool;
+pub mod watch;
+pub mod workdir;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -28,9 +47,26 @@ pub struct Cli {
     /// Arguments passed to compiler, default: -Wall -Wextra -lm
     #[arg(long)]
     compiler_args: Option<String>,
-    /// Time limits for tested program to run(in seconds), default: 10
-    #[arg(short, long)]
+    /// Time limit of tested program, like 2, 1.5s or 1500ms(number alone is in seconds),
+    /// default: time limit of test config, or 10s
+    #[arg(short, long, value_parser = parse_time)]
     timeout: Option<u64>,
+    /// Time limit of std program, like --timeout, default: same as tested program
+    #[arg(long, value_parser = parse_time, conflicts_with = "std_multiplier")]
+    std_timeout: Option<u64>,
+    /// Time limit of std program as multiple of the one of tested program, like 3 or 2.5x
+    #[arg(long, value_parser = parse_multiplier)]
+    std_multiplier: Option<f64>,
+    /// Run tested program in sandbox(Linux only): no network, read-only file system except
+    /// its working directory, limited processes, and forbidden system calls fail with SV
+    #[arg(long)]
+    sandbox: bool,
+    /// Most tests expanded from glob patterns, default: 1000000
+    #[arg(long, global = true)]
+    max_combinations: Option<u128>,
+    /// Run randomly chosen tests when glob patterns expand to more than --max-combinations
+    #[arg(long, global = true)]
+    sample: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -43,6 +79,12 @@ enum Commands {
         source: String,
         /// Path of the test config file, default: foo.test for source foo.c
         test_config: Option<String>,
+        /// Read tests from directory of input and answer files, instead of test config
+        #[arg(long, conflicts_with = "test_config")]
+        data_dir: Option<String>,
+        /// How input and answer files in --data-dir are named
+        #[arg(long, value_enum, requires = "data_dir")]
+        layout: Option<DataLayout>,
     },
     /// Compare program's output with that from "standard program", with every possible input defined in test config file
     Pal {
@@ -61,6 +103,50 @@ enum Commands {
         std_source: Option<String>,
         /// Path of the test config file, default: foo.test for source foo.c
         test_config: Option<String>,
+        /// Seed for generating random inputs, default: chosen randomly
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Only generate and run the test with given job id, showing its output
+        #[arg(long, requires = "seed")]
+        only: Option<usize>,
+    },
+    /// Keep running random generated tests like RandomPal, until a failed test is found
+    Stress {
+        /// Path of the source of the program to test
+        source: String,
+        /// Path of the source of the "standard program", default: foo_std.c for foo.c
+        std_source: Option<String>,
+        /// Path of the test config file(same as RandomPal, number of tests is ignored), default: foo.test for source foo.c
+        test_config: Option<String>,
+        /// Number of tests to generate and run in a batch, default: 1000
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+        /// Stop after running given number of tests
+        #[arg(short = 'n', long)]
+        max_tests: Option<usize>,
+        /// Stop after running for given time(in seconds)
+        #[arg(long)]
+        max_time: Option<u64>,
+        /// Stop after finding given number of failed tests, default: 1
+        #[arg(short = 'f', long)]
+        max_failures: Option<usize>,
+        /// Seed for generating random inputs, default: chosen randomly
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Check test config without running anything, and print number of tests from each input
+    Lint {
+        /// Path of the test config file, or directory of test data
+        test_config: String,
+        /// Type of test config(check, pal, random-pal), default: guessed from its first line
+        #[arg(short = 't', long = "type")]
+        config_type: Option<String>,
+    },
+    /// Rerun tests of previous test whenever source, std source or test config is modified
+    Watch {
+        /// Interval between checks for modification(in milliseconds), default: 500
+        #[arg(short, long)]
+        interval: Option<u64>,
     },
     /// Access test results of previous test, or recheck after fixing bugs
     Session {
@@ -79,11 +165,42 @@ enum SessionCommands {
         /// Specify type of failing reason to load(WA, TLE, REG, OE)
         #[arg(short = 't', long)]
         job_type: Option<String>,
+        /// Load the test with given job id, whether it passed or not
+        #[arg(long, conflicts_with_all = ["num", "job_type", "all", "random"])]
+        id: Option<usize>,
+        /// Load all tests of given type which haven't been shown
+        #[arg(short, long, conflicts_with = "num")]
+        all: bool,
+        /// Mark all failed tests as not shown before loading
+        #[arg(long)]
+        reset: bool,
+        /// Choose tests to load randomly instead of in order
+        #[arg(long)]
+        random: bool,
+    },
+    /// List job id, result and input preview of failed tests
+    Show {
+        /// List passed tests as well
+        #[arg(short, long)]
+        all: bool,
+    },
+    /// Rerun a single test with current source, showing program output as it runs
+    Run {
+        /// Id of the test to run
+        job_id: usize,
     },
     /// Retest failed tests after fixing bugs
-    Continue,
+    Continue {
+        /// Recompile std program and regenerate expected output(Pal and RandomPal only)
+        #[arg(short, long)]
+        regenerate: bool,
+    },
     /// Retest accepted and failed tests after fixing bugs
-    Retest,
+    Retest {
+        /// Recompile std program and regenerate expected output(Pal and RandomPal only)
+        #[arg(short, long)]
+        regenerate: bool,
+    },
 }
 pub enum CLIError {
     InvalidArgument(String),
@@ -107,75 +224,99 @@ impl std::fmt::Debug for CLIError {
     }
 }
 
-pub fn run_from_session(session: Session) -> Result<(), CLIError> {
-    let current_working_directory = Path::new(&session.current_working_directory).to_path_buf();
+/// Settings defined in test config besides tests, with programs relative to test config
+#[derive(Default)]
+pub(crate) struct ConfigSettings {
+    pub generator_source: Option<String>,
+    pub checker_source: Option<String>,
+    pub comparator: Comparator,
+    /// Time limit of tests in milliseconds, used unless `--timeout` is given
+    pub timeout: Option<u64>,
+    pub file_io: Option<FileIo>,
+    /// Files and directories copied to working directory of every job
+    pub fixtures: Vec<String>,
+}
 
-    println!(
-        "Current working directory: {}",
-        current_working_directory.to_str().unwrap()
-    );
+pub(crate) fn config_settings(
+    test_config: &TestConfig,
+    test_config_str: &str,
+) -> Result<ConfigSettings, CLIError> {
+    let resolve = |source: &str| {
+        test_config
+            .base()
+            .join(source)
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let problem = test_config
+        .problem(test_config_str)
+        .map_err(|e| CLIError::ParseError(format!("{:?}", e)))?;
+    Ok(match problem {
+        Some(problem) => ConfigSettings {
+            generator_source: problem
+                .generator
+                .map(|generator| resolve(&generator.source)),
+            checker_source: problem.checker.map(|checker| resolve(&checker.source)),
+            comparator: problem.comparator,
+            timeout: problem.limits.time,
+            file_io: problem.files,
+            fixtures: problem
+                .fixtures
+                .iter()
+                .map(|fixture| resolve(fixture))
+                .collect(),
+        },
+        None => ConfigSettings {
+            generator_source: parse_generator_source(test_config_str)
+                .map(|generator_source| resolve(&generator_source)),
+            ..Default::default()
+        },
+    })
+}
 
-    let compiler = session.compiler;
-    let compiler_arguments = session.compiler_arguments;
+impl ConfigSettings {
+    /// Time limit of tests in milliseconds: `timeout_ms` given by `--timeout`, or else the one
+    /// of test config, or else 10s
+    pub(crate) fn timeout_ms(&self, timeout_ms: Option<u64>) -> u64 {
+        timeout_ms
+            .or(self.timeout)
+            .unwrap_or(session::DEFAULT_TIMEOUT_MS)
+    }
+}
 
-    let timeout = session.timeout;
+/// Settings of test config of session, or default ones if it can't be read, so stored tests
+/// can be rerun after test config is removed
+pub(crate) fn session_settings(session: &Session) -> Result<ConfigSettings, CLIError> {
+    let test_config = session.test_config();
+    match test_config.read() {
+        Ok(test_config_str) => config_settings(&test_config, &test_config_str),
+        Err(_) => Ok(ConfigSettings::default()),
+    }
+}
 
-    let test_config = session.test_config_filename;
+pub fn run_from_session(session: Session) -> Result<(), CLIError> {
+    println!(
+        "Current working directory: {}",
+        session.current_working_directory
+    );
 
-    let test_config_path = current_working_directory.join(&test_config);
+    let test_config = session.test_config();
+    let test_config_str = test_config.read().map_err(CLIError::IOError)?;
 
-    let test_config_str = fs::read_to_string(test_config_path)
-        .map_err(|e| CLIError::IOError(format!("Cannot read test config: {:?}", e)))?;
+    let settings = config_settings(&test_config, &test_config_str)?;
+    let timeout = settings.timeout_ms(session.timeout_ms);
 
     match session.pal_type {
-        PalType::Check => {
-            let compiler_config = CompileConfig {
-                compiler,
-                args: compiler_arguments,
-                source: session.source,
-                std_source: None,
-                work_directory: current_working_directory.to_str().unwrap().to_string(),
-            };
-            run_pal(
-                pal::PalType::Check,
-                compiler_config,
-                &test_config_str,
-                session.job_store_filepath,
-                timeout,
-            )
-            .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))?;
-        }
-        PalType::Pal => {
-            let compiler_config = CompileConfig {
-                compiler,
-                args: compiler_arguments,
-                source: session.source,
-                std_source: Some(session.std_source),
-                work_directory: current_working_directory.to_str().unwrap().to_string(),
-            };
-            run_pal(
-                pal::PalType::Pal,
-                compiler_config,
-                &test_config_str,
-                session.job_store_filepath,
-                timeout,
-            )
-            .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))?;
-        }
-        PalType::RandomPal => {
-            let compiler_config = CompileConfig {
-                compiler,
-                args: compiler_arguments,
-                source: session.source,
-                std_source: Some(session.std_source),
-                work_directory: current_working_directory.to_str().unwrap().to_string(),
-            };
+        PalType::Check | PalType::Pal | PalType::RandomPal => {
             run_pal(
-                pal::PalType::RandomPal,
-                compiler_config,
-                &test_config_str,
+                session.pal_type.clone(),
+                CompileConfig::from_session(&session, &settings),
+                &test_config,
                 session.job_store_filepath,
                 timeout,
+                session.seed,
+                session.glob_limit,
             )
             .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))?;
         }
@@ -185,6 +326,140 @@ pub fn run_from_session(session: Session) -> Result<(), CLIError> {
     Ok(())
 }
 
+/// Type of retest, config to compile programs with and time limit of tests
+pub(crate) fn build_retest_config(
+    session: &Session,
+    regenerate: bool,
+) -> Result<(PalType, CompileConfig, u64), CLIError> {
+    let (pal_type, std_source) = match (&session.pal_type, regenerate) {
+        (_, false) => {
+            if let PalType::Pal | PalType::RandomPal = session.pal_type {
+                println!(
+                    "Comparing with saved output of std program, use --regenerate to rerun it."
+                );
+            }
+            (PalType::Retest, None)
+        }
+        (PalType::Pal | PalType::RandomPal, true) => {
+            (session.pal_type.clone(), Some(session.std_source.clone()))
+        }
+        (pal_type, true) => {
+            return Err(CLIError::InvalidArgument(format!(
+                "Cannot regenerate expected output for type: {}",
+                pal_type
+            )))
+        }
+    };
+
+    let settings = session_settings(session)?;
+    let timeout = settings.timeout_ms(session.timeout_ms);
+    Ok((
+        pal_type,
+        // Stored inputs are rerun instead of generated again
+        CompileConfig {
+            std_source,
+            generator_source: None,
+            ..CompileConfig::from_session(session, &settings)
+        },
+        timeout,
+    ))
+}
+
+pub fn run_stress_from_session(
+    session: Session,
+    stress_config: StressConfig,
+) -> Result<(), CLIError> {
+    println!(
+        "Current working directory: {}",
+        session.current_working_directory
+    );
+
+    let test_config = session.test_config();
+    let test_config_str = test_config.read().map_err(CLIError::IOError)?;
+    let settings = config_settings(&test_config, &test_config_str)?;
+    let timeout = settings.timeout_ms(session.timeout_ms);
+
+    let compile_config = CompileConfig::from_session(&session, &settings);
+    run_stress(
+        compile_config,
+        &test_config,
+        session.job_store_filepath,
+        timeout,
+        session.seed,
+        stress_config,
+    )
+    .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))?;
+
+    Ok(())
+}
+
+pub fn run_only_from_session(session: Session, job_id: usize) -> Result<(), CLIError> {
+    let test_config = session.test_config();
+    let test_config_str = test_config.read().map_err(CLIError::IOError)?;
+    let settings = config_settings(&test_config, &test_config_str)?;
+
+    let job = test_config
+        .random_pal_inputs(&test_config_str)
+        .and_then(|blocks| random_pal_job(&blocks, session.seed, job_id))
+        .map_err(|e| CLIError::ParseError(format!("{:?}", test_config.locate(e))))?;
+    let timeout = settings.timeout_ms(session.timeout_ms);
+
+    let compile_config = CompileConfig::from_session(&session, &settings);
+    run_single(
+        PalType::RandomPal,
+        compile_config,
+        job,
+        &session.job_store_filepath,
+        timeout,
+    )
+    .map_err(|e| CLIError::PalError(format!("Error while running test: {:?}", e)))?;
+
+    Ok(())
+}
+
+/// Retest failed tests of previous test by `session continue`, or passed ones as well by
+/// `session retest`
+pub(crate) fn retest_from_session(
+    session: &Session,
+    pal_store: &PalStore,
+    all: bool,
+    regenerate: bool,
+) -> Result<(), CLIError> {
+    let (pal_type, compile_config, timeout) = build_retest_config(session, regenerate)?;
+    let mut job_list = Vec::new();
+    let mut base = RetestBase {
+        job_passed: Vec::new(),
+        subtasks: pal_store.subtask_list(),
+        seed: pal_store.seed,
+    };
+    if all {
+        pal_store
+            .job_passed
+            .iter()
+            .for_each(|(job, _, _)| job_list.push(job.clone()));
+    } else {
+        // Passed tests aren't run again, but still count in subtasks and groups
+        base.job_passed = pal_store.job_passed.clone();
+    }
+    pal_store
+        .job_failed
+        .iter()
+        .for_each(|(job, _, _)| job_list.push(job.clone()));
+    if job_list.is_empty() && !all {
+        println!("No failed test to run.");
+        return Ok(());
+    }
+    run_retest(
+        pal_type,
+        compile_config,
+        job_list,
+        base,
+        &session.job_store_filepath,
+        timeout,
+    )
+    .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))
+}
+
 pub fn run(cli: Cli) -> Result<(), CLIError> {
     println!(
         "Running on: {}, CPU cores: {}",
@@ -198,56 +473,27 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
     println!("Data directory: {}", data_dir.to_str().unwrap());
     match cli.command {
         Commands::Session { subcommand } => {
-            let session_path = data_dir.to_path_buf().join("session.json");
-            if !session_path.exists() {
-                return Err(CLIError::InvalidArgument(format!(
-                    "Session file: {} does not exists. Run a test first.",
-                    session_path.to_str().unwrap()
-                )));
-            }
-            let session: Session =
-                serde_json::from_reader(File::open(&session_path).map_err(|e| {
-                    CLIError::IOError(format!(
-                        "Cannot open session file: {} for {:?}",
-                        session_path.to_str().unwrap(),
-                        e
-                    ))
-                })?)
-                .map_err(|e| CLIError::ParseError(format!("{:?}", e)))?;
+            let session = load_session(data_dir)?;
             println!("Session id: {}", session.uuid);
             let job_store_filepath = &session.job_store_filepath;
             println!("Reading results from: {}...", job_store_filepath);
-            let mut pal_store: PalStore = serde_json::from_reader(
-                File::open(job_store_filepath)
-                    .map_err(|e| CLIError::IOError(format!("Cannot reading results: {:?}", e)))?,
-            )
-            .map_err(|e| CLIError::ParseError(format!("Cannot parsing results file: {:?}", e)))?;
+            let mut pal_store = load_store(job_store_filepath)?;
 
             match subcommand {
                 Some(subcommand) => match subcommand {
-                    SessionCommands::Load { num, job_type } => {
-                        if pal_store.job_failed.len() == 0 {
-                            println!("No failed test to load.");
-                        } else {
-                            let num = num.unwrap_or(1);
-                            let default_job_type = pal_store.job_failed[0].1.to_string();
-                            let job_type = job_type.unwrap_or(default_job_type);
-                            println!("job_type: {}", job_type);
-                            let mut shown_count = 0;
-                            for (job, job_result, shown) in &mut pal_store.job_failed {
-                                if job_result.to_string() == job_type && !*shown {
-                                    *shown = true;
-                                    shown_count += 1;
-                                    show_job((&job, &job_result), &session.test_info_directory)?;
-                                }
-                                if shown_count >= num {
-                                    break;
-                                }
-                            }
-
-                            if shown_count == 0 {
-                                println!("No such job type: {}", job_type);
-                            }
+                    SessionCommands::Load {
+                        num,
+                        job_type,
+                        id,
+                        all,
+                        reset,
+                        random,
+                    } => {
+                        let num = if all { usize::MAX } else { num.unwrap_or(1) };
+                        let timeout_ms = pal_store.pal_info.timeout_ms;
+                        for id in load_jobs(&mut pal_store, id, job_type, num, reset, random) {
+                            let (job, job_result, _) = find_job(&mut pal_store, id).unwrap();
+                            show_job((job, job_result), &session.test_info_directory, timeout_ms)?;
                         }
                         serde_json::to_writer(
                             File::create(job_store_filepath).map_err(|e| {
@@ -259,60 +505,41 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
                             CLIError::IOError(format!("Cannot writing results: {:?}", e))
                         })?;
                     }
-                    SessionCommands::Continue => {
-                        let compile_config = CompileConfig {
-                            compiler: session.compiler.clone(),
-                            args: session.compiler_arguments.clone(),
-                            source: session.source.clone(),
-                            std_source: None,
-                            work_directory: session.current_working_directory.clone(),
-                        };
-                        let mut job_list = Vec::new();
-                        pal_store
-                            .job_failed
+                    SessionCommands::Show { all } => {
+                        show_table(&pal_store, all)
                             .iter()
-                            .for_each(|(job, _, _)| job_list.push(job.clone()));
-                        if job_list.len() == 0 {
-                            println!("No failed test to run.")
-                        } else {
-                            run_retest(
-                                compile_config,
-                                job_list,
-                                &session.job_store_filepath,
-                                session.timeout,
-                            )
-                            .map_err(|e| {
-                                CLIError::PalError(format!("Error while running tests: {:?}", e))
-                            })?;
-                        }
+                            .for_each(|line| println!("{}", line));
                     }
-                    SessionCommands::Retest => {
+                    SessionCommands::Run { job_id } => {
+                        let job = find_job(&mut pal_store, job_id)
+                            .map(|(job, _, _)| job.clone())
+                            .ok_or_else(|| {
+                                CLIError::InvalidArgument(format!("No such job id: {}", job_id))
+                            })?;
+                        let settings = session_settings(&session)?;
+                        let timeout = settings.timeout_ms(session.timeout_ms);
+                        // Stored input is rerun instead of generated again
                         let compile_config = CompileConfig {
-                            compiler: session.compiler.clone(),
-                            args: session.compiler_arguments.clone(),
-                            source: session.source.clone(),
-                            std_source: None,
-                            work_directory: session.current_working_directory.clone(),
+                            generator_source: None,
+                            ..CompileConfig::from_session(&session, &settings)
                         };
-                        let mut job_list = Vec::new();
-                        pal_store
-                            .job_passed
-                            .iter()
-                            .for_each(|(job, _, _)| job_list.push(job.clone()));
-                        pal_store
-                            .job_failed
-                            .iter()
-                            .for_each(|(job, _, _)| job_list.push(job.clone()));
-                        run_retest(
+                        run_single(
+                            session.pal_type.clone(),
                             compile_config,
-                            job_list,
+                            job,
                             &session.job_store_filepath,
-                            session.timeout,
+                            timeout,
                         )
                         .map_err(|e| {
-                            CLIError::PalError(format!("Error while running tests: {:?}", e))
+                            CLIError::PalError(format!("Error while running test: {:?}", e))
                         })?;
                     }
+                    SessionCommands::Continue { regenerate } => {
+                        retest_from_session(&session, &pal_store, false, regenerate)?
+                    }
+                    SessionCommands::Retest { regenerate } => {
+                        retest_from_session(&session, &pal_store, true, regenerate)?
+                    }
                 },
                 None => {
                     println!(
@@ -321,9 +548,19 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
                         pal_store.job_failed.len()
                     );
 
+                    print_groups(&pal_store.groups());
+                    print_subtasks(&pal_store.subtasks);
+
+                    if let Some(seed) = pal_store.seed {
+                        println!("Seed: {}", seed);
+                    }
+                    if let Some(std_hash) = &pal_store.pal_info.std_hash {
+                        println!("Std program hash: {}", std_hash);
+                    }
+
                     let mut failed_by_type = HashMap::new();
 
-                    if pal_store.job_failed.len() > 0 {
+                    if !pal_store.job_failed.is_empty() {
                         println!("Of failed tests: ");
                         pal_store
                             .job_failed
@@ -342,6 +579,20 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
                 }
             }
         }
+        Commands::Lint {
+            test_config,
+            config_type,
+        } => lint(
+            &test_config,
+            config_type.as_deref(),
+            cli.max_combinations
+                .unwrap_or(GlobLimit::default().max_combinations),
+        )?,
+        Commands::Watch { interval } => {
+            let session = load_session(data_dir)?;
+            println!("Session id: {}", session.uuid);
+            watch::run_watch(session, Duration::from_millis(interval.unwrap_or(500)))?;
+        }
         _ => {
             let create_data_directory = fs::create_dir_all(data_dir);
 
@@ -355,10 +606,41 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
                 )));
             }
 
+            let stress_config = match &cli.command {
+                Commands::Stress {
+                    batch_size,
+                    max_tests,
+                    max_time,
+                    max_failures,
+                    ..
+                } => Some(StressConfig {
+                    batch_size: batch_size.unwrap_or(1000).max(1),
+                    max_tests: *max_tests,
+                    max_time: max_time.map(Duration::from_secs),
+                    max_failures: max_failures.unwrap_or(1).max(1),
+                }),
+                _ => None,
+            };
+            let only = match &cli.command {
+                Commands::RandomPal { only, .. } => *only,
+                _ => None,
+            };
+
             let mut session = Session::build(cli, data_dir.to_path_buf())?;
             println!("Session id: {}", session.uuid);
+            if let PalType::RandomPal = session.pal_type {
+                println!("Seed: {}", session.seed);
+            }
+
+            if let Some(job_id) = only {
+                // Running a single test doesn't replace previous session
+                return run_only_from_session(session, job_id);
+            }
 
-            run_from_session(session.clone())?;
+            match stress_config {
+                Some(stress_config) => run_stress_from_session(session.clone(), stress_config)?,
+                None => run_from_session(session.clone())?,
+            }
             session.run = true;
             fs::write(
                 data_dir.to_path_buf().join("session.json"),
@@ -371,12 +653,228 @@ pub fn run(cli: Cli) -> Result<(), CLIError> {
     Ok(())
 }
 
+/// Stored test with given job id, whether it passed or not
+fn find_job(pal_store: &mut PalStore, id: usize) -> Option<&mut (Job, JobResult, bool)> {
+    pal_store
+        .job_failed
+        .iter_mut()
+        .chain(pal_store.job_passed.iter_mut())
+        .find(|(job, _, _)| job.id == id)
+}
+
+/// Ids of tests to load by `session load`, which are marked as shown so each failed test is
+/// loaded once until `reset`. Test of `id` is loaded even if it's shown or passed.
+fn load_jobs(
+    pal_store: &mut PalStore,
+    id: Option<usize>,
+    job_type: Option<String>,
+    num: usize,
+    reset: bool,
+    random: bool,
+) -> Vec<usize> {
+    if reset {
+        pal_store
+            .job_failed
+            .iter_mut()
+            .for_each(|(_, _, shown)| *shown = false);
+        println!(
+            "Reset shown state of {} failed tests.",
+            pal_store.job_failed.len()
+        );
+    }
+    if let Some(id) = id {
+        return match find_job(pal_store, id) {
+            Some((_, _, shown)) => {
+                *shown = true;
+                vec![id]
+            }
+            None => {
+                println!("No such job id: {}", id);
+                Vec::new()
+            }
+        };
+    }
+    if pal_store.job_failed.is_empty() {
+        println!("No failed test to load.");
+        return Vec::new();
+    }
+
+    let default_job_type = pal_store.job_failed[0].1.to_string();
+    let job_type = job_type.unwrap_or(default_job_type);
+    println!("job_type: {}", job_type);
+    let mut to_show: Vec<usize> = pal_store
+        .job_failed
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, job_result, shown))| job_result.to_string() == job_type && !*shown)
+        .map(|(index, _)| index)
+        .collect();
+    if random {
+        to_show.shuffle(&mut thread_rng());
+    }
+    to_show.truncate(num);
+
+    if to_show.is_empty() {
+        if pal_store
+            .job_failed
+            .iter()
+            .any(|(_, job_result, _)| job_result.to_string() == job_type)
+        {
+            println!(
+                "All tests of type {} have been shown, use --reset to show them again.",
+                job_type
+            );
+        } else {
+            println!("No such job type: {}", job_type);
+        }
+    }
+    to_show
+        .into_iter()
+        .map(|index| {
+            let (job, _, shown) = &mut pal_store.job_failed[index];
+            *shown = true;
+            job.id
+        })
+        .collect()
+}
+
+/// Lines listing stored tests by `session show`, failed ones only unless `all`
+fn show_table(pal_store: &PalStore, all: bool) -> Vec<String> {
+    let mut job_list: Vec<&(Job, JobResult, bool)> = pal_store.job_failed.iter().collect();
+    if all {
+        job_list.extend(pal_store.job_passed.iter());
+    }
+    job_list.sort_by_key(|(job, _, _)| job.id);
+
+    if job_list.is_empty() {
+        return vec![String::from("No test to show.")];
+    }
+    let mut lines = vec![format!(
+        "{:<8} {:<10} {:<6} {:<16} {:<20} INPUT",
+        "ID", "RESULT", "SHOWN", "TIME", "NAME"
+    )];
+    for (job, job_result, shown) in job_list {
+        lines.push(format!(
+            "{:<8} {:<10} {:<6} {:<16} {:<20} {}",
+            job.id,
+            result_name(job_result),
+            if *shown { "yes" } else { "no" },
+            job.limits_report(pal_store.pal_info.timeout_ms),
+            job.full_name().unwrap_or_else(|| String::from("-")),
+            input_preview(&job.input, 40)
+        ));
+    }
+    lines
+}
+
+pub fn lint(
+    test_config: &str,
+    config_type: Option<&str>,
+    max_combinations: u128,
+) -> Result<(), CLIError> {
+    let is_data_dir = Path::new(test_config).is_dir();
+    let test_config = TestConfig {
+        path: test_config.to_string(),
+        data_layout: is_data_dir.then_some(DataLayout::Auto),
+    };
+    let test_config_str = test_config.read().map_err(CLIError::IOError)?;
+    let problem = test_config
+        .problem(&test_config_str)
+        .map_err(|e| CLIError::ParseError(format!("{:?}", e)))?;
+
+    let first_line = test_config_str.lines().next().unwrap_or("");
+    let pal_type = match config_type {
+        Some("check") => PalType::Check,
+        Some("pal") => PalType::Pal,
+        Some("random-pal") => PalType::RandomPal,
+        Some(x) => {
+            return Err(CLIError::InvalidArgument(format!(
+                "Unknown config type: {}, should be check, pal or random-pal",
+                x
+            )))
+        }
+        None if is_data_dir => PalType::Check,
+        None => match &problem {
+            Some(problem) => problem.guess_type(),
+            None if matches!(first_line, "simple" | "glob" | "generator") => PalType::Pal,
+            None if first_line.parse::<usize>().is_ok() => PalType::RandomPal,
+            None => PalType::Check,
+        },
+    };
+    if is_data_dir {
+        println!("Checking {} as test data directory...", test_config.path);
+    } else {
+        println!("Checking {} as config of {}...", test_config.path, pal_type);
+    }
+
+    // Combinations are counted without expanding, so huge glob patterns are checked quickly
+    let blocks = test_config
+        .count_blocks(&pal_type, &test_config_str, max_combinations)
+        .map_err(|e| CLIError::ParseError(format!("{:?}", test_config.locate(e))))?;
+    for (label, count) in blocks.iter().filter(|_| !is_data_dir) {
+        println!(
+            "{}: {} tests",
+            label,
+            format_count(*count, max_combinations)
+        );
+    }
+    let total = blocks
+        .iter()
+        .fold(0u128, |total, (_, count)| total.saturating_add(*count));
+    if is_data_dir {
+        println!("OK: {} tests", format_count(total, max_combinations));
+    } else {
+        println!(
+            "OK: {} tests from {} inputs",
+            format_count(total, max_combinations),
+            blocks.len()
+        );
+    }
+    if total > max_combinations {
+        println!("Running it needs --sample, or a higher limit with --max-combinations");
+    }
+    Ok(())
+}
+
+pub fn load_session(data_dir: &Path) -> Result<Session, CLIError> {
+    let session_path = data_dir.to_path_buf().join("session.json");
+    if !session_path.exists() {
+        return Err(CLIError::InvalidArgument(format!(
+            "Session file: {} does not exists. Run a test first.",
+            session_path.to_str().unwrap()
+        )));
+    }
+    let session_content = fs::read_to_string(&session_path).map_err(|e| {
+        CLIError::IOError(format!(
+            "Cannot open session file: {} for {:?}",
+            session_path.to_str().unwrap(),
+            e
+        ))
+    })?;
+    Session::parse(&session_content).map_err(|e| CLIError::ParseError(format!("{:?}", e)))
+}
+
+pub fn load_store(job_store_filepath: &str) -> Result<PalStore, CLIError> {
+    let store_content = fs::read_to_string(job_store_filepath)
+        .map_err(|e| CLIError::IOError(format!("Cannot reading results: {:?}", e)))?;
+    pal::parse_store(&store_content)
+        .map_err(|e| CLIError::ParseError(format!("Cannot parsing results file: {:?}", e)))
+}
+
 pub fn show_job(
     job_info: (&Job, &JobResult),
     test_info_directory: &String,
+    timeout_ms: u64,
 ) -> Result<(), CLIError> {
     let (job, job_result) = job_info;
-    println!("{}(Job id = {})", job_result, job.id);
+    println!("{}(Job id = {})", result_name(job_result), job.label());
+    println!("Time: {}", job.limits_report(timeout_ms));
+    if !job.args.is_empty() {
+        println!("Arguments: {}", job.args.join(" "));
+    }
+    for (key, value) in &job.env {
+        println!("Environment: {}={}", key, value);
+    }
     let test_info_directory = Path::new(test_info_directory).to_path_buf();
 
     let create_result = fs::create_dir(test_info_directory.join(format!("{}", job.id)));
@@ -404,6 +902,10 @@ pub fn show_job(
         .join(format!("{}", job.id))
         .join("expected_out.txt");
 
+    let kept_dir = job::kept_dir(test_info_directory.to_str().unwrap(), job.id);
+    if kept_dir.is_dir() {
+        println!("Working directory: {}", kept_dir.to_str().unwrap());
+    }
     println!("Input file: {}", in_path.to_str().unwrap());
     fs::write(&in_path, &job.input).map_err(|e| {
         CLIError::IOError(format!(
@@ -426,6 +928,12 @@ pub fn show_job(
         "Expected output file: {}",
         expected_out_path.to_str().unwrap()
     );
+    if let Some(seed) = job.seed {
+        println!("Seed of input: {}", seed);
+    }
+    if let Some(std_hash) = &job.std_hash {
+        println!("Expected output generated by std program: {}", std_hash);
+    }
     fs::write(&expected_out_path, &job.expected_output).map_err(|e| {
         CLIError::IOError(format!(
             "Cannot write to {} for {:?}",
@@ -436,3 +944,338 @@ pub fn show_job(
 
     Ok(())
 }
+
+/// Name of job result in reports, accepted tests are shown as "." in progress output
+pub(crate) fn result_name(job_result: &JobResult) -> String {
+    match job_result {
+        JobResult::Accepted => String::from("AC"),
+        _ => job_result.to_string(),
+    }
+}
+
+/// Single-line preview of job input, with newlines escaped and long input truncated
+pub fn input_preview(input: &[u8], max_len: usize) -> String {
+    let escaped = String::from_utf8_lossy(input)
+        .trim_end()
+        .replace('\n', "\\n");
+    if escaped.chars().count() > max_len {
+        let mut preview: String = escaped.chars().take(max_len).collect();
+        preview.push_str("...");
+        preview
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Store of failed jobs 0, 1(WA) and 2(TLE), and passed job 3
+    fn test_store() -> PalStore {
+        let job = |id: usize| Job {
+            id,
+            input: format!("{}\n", id).into_bytes(),
+            ..Default::default()
+        };
+        PalStore {
+            job_passed: vec![(job(3), JobResult::Accepted, false)],
+            job_failed: vec![
+                (job(0), JobResult::WrongAnswer, false),
+                (job(1), JobResult::WrongAnswer, false),
+                (job(2), JobResult::TimeLimitExceed, false),
+            ],
+            pal_info: serde_json::from_str(
+                r#"{"prog": "", "work_directory": "", "out_directory": "",
+                "test_info_directory": "", "job_store_filepath": "", "std": null,
+                "timeout_ms": 1000}"#,
+            )
+            .unwrap(),
+            seed: None,
+            subtasks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_load_jobs() {
+        let mut store = test_store();
+        // Failed tests of type of first one are loaded once each
+        assert_eq!(load_jobs(&mut store, None, None, 1, false, false), [0]);
+        assert_eq!(load_jobs(&mut store, None, None, 1, false, false), [1]);
+        assert!(load_jobs(&mut store, None, None, 1, false, false).is_empty());
+        let tle = Some(String::from("TLE"));
+        assert_eq!(
+            load_jobs(&mut store, None, tle.clone(), 5, false, false),
+            [2]
+        );
+        assert!(load_jobs(&mut store, None, tle, 5, false, false).is_empty());
+        assert!(store.job_failed.iter().all(|(_, _, shown)| *shown));
+
+        // Reset shows them again
+        assert_eq!(
+            load_jobs(&mut store, None, None, usize::MAX, true, false),
+            [0, 1]
+        );
+        let mut random = load_jobs(&mut store, None, None, usize::MAX, true, true);
+        random.sort();
+        assert_eq!(random, [0, 1]);
+
+        // Any test is loaded by id, even if it's shown or passed
+        assert_eq!(load_jobs(&mut store, Some(0), None, 1, false, false), [0]);
+        assert_eq!(load_jobs(&mut store, Some(3), None, 1, false, false), [3]);
+        assert!(store.job_passed[0].2);
+        assert!(load_jobs(&mut store, Some(9), None, 1, false, false).is_empty());
+    }
+
+    #[test]
+    fn test_show_table() {
+        let mut store = test_store();
+        load_jobs(&mut store, None, None, 1, false, false);
+
+        let lines = show_table(&store, false);
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("ID"));
+        let columns: Vec<&str> = lines[1].split_whitespace().collect();
+        assert_eq!(columns[..3], ["0", "WA", "yes"]);
+        assert_eq!(columns[4..], ["-", "0"]);
+        assert!(lines[2].starts_with("1 ") && lines[2].contains(" no "));
+
+        // Named test shows its name along with input
+        store.job_failed[1].0.group = Some(String::from("large"));
+        store.job_failed[1].0.name = Some(String::from("1.in"));
+        let lines = show_table(&store, false);
+        let columns: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(columns[4..], ["large/1.in", "1"]);
+
+        let lines = show_table(&store, true);
+        assert_eq!(lines.len(), 5);
+        assert!(lines[4].starts_with("3 ") && lines[4].contains(" AC "));
+        assert_eq!(JobResult::Accepted.to_string(), ".");
+
+        store.job_failed.clear();
+        assert_eq!(show_table(&store, false), ["No test to show."]);
+    }
+
+    #[test]
+    fn test_build_retest_config() {
+        let session = |args: &[&str]| {
+            let cli = Cli::parse_from(["rpal"].iter().chain(args));
+            Session::build(cli, env::temp_dir()).unwrap()
+        };
+        let random_pal = session(&[
+            "random-pal",
+            "tests/pal/stress/wa.c",
+            "tests/pal/stress/std.c",
+            "tests/pal/stress/stress.test",
+        ]);
+
+        // Stored expected output is compared with unless it's regenerated by std program
+        let (pal_type, compile_config, _) = build_retest_config(&random_pal, false).unwrap();
+        assert!(matches!(pal_type, PalType::Retest));
+        assert_eq!(compile_config.std_source, None);
+        let (pal_type, compile_config, _) = build_retest_config(&random_pal, true).unwrap();
+        assert!(matches!(pal_type, PalType::RandomPal));
+        assert_eq!(
+            compile_config.std_source,
+            Some(random_pal.std_source.clone())
+        );
+
+        let check = session(&[
+            "check",
+            "tests/pal/check/success.c",
+            "tests/pal/check/success.test",
+        ]);
+        assert!(build_retest_config(&check, false).is_ok());
+        assert!(matches!(
+            build_retest_config(&check, true),
+            Err(CLIError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_compile_config_from_session() {
+        let session = |args: &[&str]| {
+            let cli = Cli::parse_from(["rpal"].iter().chain(args));
+            Session::build(cli, env::temp_dir()).unwrap()
+        };
+        let settings = ConfigSettings {
+            generator_source: Some(String::from("gen.c")),
+            checker_source: Some(String::from("checker.c")),
+            fixtures: vec![String::from("data")],
+            ..Default::default()
+        };
+
+        let check = session(&[
+            "check",
+            "tests/pal/check/success.c",
+            "tests/pal/check/success.test",
+        ]);
+        let compile_config = CompileConfig::from_session(&check, &settings);
+        assert_eq!(compile_config.source, check.source);
+        assert_eq!(compile_config.std_source, None);
+        assert_eq!(compile_config.generator_source, None);
+        assert_eq!(compile_config.checker_source, settings.checker_source);
+        assert_eq!(compile_config.fixtures, settings.fixtures);
+        assert_eq!(
+            compile_config.work_directory,
+            check.current_working_directory
+        );
+
+        // Generator program is only used by Pal
+        let pal = session(&[
+            "pal",
+            "tests/pal/pal/success.c",
+            "tests/pal/pal/success_std.c",
+            "tests/pal/pal/success.test",
+        ]);
+        let compile_config = CompileConfig::from_session(&pal, &settings);
+        assert_eq!(compile_config.std_source, Some(pal.std_source.clone()));
+        assert_eq!(compile_config.generator_source, settings.generator_source);
+        let random_pal = session(&[
+            "random-pal",
+            "tests/pal/random_pal/success.c",
+            "tests/pal/random_pal/success_std.c",
+            "tests/pal/random_pal/success.test",
+        ]);
+        let compile_config = CompileConfig::from_session(&random_pal, &settings);
+        assert_eq!(
+            compile_config.std_source,
+            Some(random_pal.std_source.clone())
+        );
+        assert_eq!(compile_config.generator_source, None);
+    }
+
+    #[test]
+    fn test_continue_regenerate_stale_std_hash() {
+        let cli = Cli::parse_from([
+            "rpal",
+            "random-pal",
+            "tests/pal/retest/add.c",
+            "tests/pal/retest/add_std.c",
+        ]);
+        let mut session = Session::build(cli, env::temp_dir()).unwrap();
+        let job_store_path = env::temp_dir().join(format!("rpal-{}.json", session.uuid));
+        session.job_store_filepath = job_store_path.to_str().unwrap().to_string();
+
+        // Expected output saved from an older std program
+        let mut store = test_store();
+        store.job_passed.clear();
+        store.job_failed = vec![(
+            Job {
+                id: 0,
+                input: b"5\n".to_vec(),
+                expected_output: b"0".to_vec(),
+                std_hash: Some(String::from("stale")),
+                ..Default::default()
+            },
+            JobResult::WrongAnswer,
+            false,
+        )];
+        store.pal_info.std_hash = Some(String::from("stale"));
+        let continued = |regenerate| {
+            retest_from_session(&session, &store, false, regenerate).unwrap();
+            load_store(&session.job_store_filepath).unwrap()
+        };
+
+        let retested = continued(false);
+        assert_eq!(retested.job_failed.len(), 1);
+        assert_eq!(retested.job_failed[0].0.std_hash.as_deref(), Some("stale"));
+
+        let regenerated = continued(true);
+        assert!(regenerated.job_failed.is_empty());
+        let job = &regenerated.job_passed[0].0;
+        assert_eq!(job.expected_output, b"6");
+        assert_eq!(job.std_hash, regenerated.pal_info.std_hash);
+        assert_ne!(job.std_hash.as_deref(), Some("stale"));
+
+        fs::remove_file(&job_store_path).unwrap();
+    }
+
+    #[test]
+    fn test_continue_keeps_passed_subtask() {
+        let cli = Cli::parse_from([
+            "rpal",
+            "random-pal",
+            "tests/pal/retest/add.c",
+            "tests/pal/retest/add_std.c",
+        ]);
+        let mut session = Session::build(cli, env::temp_dir()).unwrap();
+        let job_store_path = env::temp_dir().join(format!("rpal-{}.json", session.uuid));
+        session.job_store_filepath = job_store_path.to_str().unwrap().to_string();
+
+        // Subtask "a" passed before, only the failed test of "b" is run again and passes
+        let job = |id: usize, group: &str, input: &[u8], expected_output: &[u8]| Job {
+            id,
+            group: Some(group.to_string()),
+            input: input.to_vec(),
+            expected_output: expected_output.to_vec(),
+            ..Default::default()
+        };
+        let subtask = |name: &str, points, passed, failed| pal::SubtaskResult {
+            subtask: problem::Subtask {
+                name: name.to_string(),
+                points,
+                depends: Vec::new(),
+            },
+            score: 0.0,
+            passed,
+            failed,
+        };
+        let mut store = test_store();
+        store.job_passed = vec![(job(0, "a", b"1\n", b"2"), JobResult::Accepted, false)];
+        store.job_failed = vec![(job(1, "b", b"5\n", b"6"), JobResult::WrongAnswer, false)];
+        store.subtasks = vec![subtask("a", 40, 1, 0), subtask("b", 60, 0, 1)];
+        store.seed = Some(7);
+
+        retest_from_session(&session, &store, false, false).unwrap();
+        let continued = load_store(&session.job_store_filepath).unwrap();
+        fs::remove_file(&job_store_path).unwrap();
+        assert_eq!(continued.seed, Some(7));
+        assert_eq!(continued.job_passed.len(), 2);
+        let scores: Vec<(f64, usize)> = continued
+            .subtasks
+            .iter()
+            .map(|result| (result.score, result.passed))
+            .collect();
+        assert_eq!(scores, [(40.0, 1), (60.0, 1)]);
+        let groups: Vec<(Option<String>, usize)> = continued
+            .groups()
+            .into_iter()
+            .map(|group| (group.name, group.passed))
+            .collect();
+        assert_eq!(
+            groups,
+            [(Some(String::from("a")), 1), (Some(String::from("b")), 1)]
+        );
+    }
+
+    #[test]
+    fn test_timeout_precedence() {
+        let timeout = |args: &[&str]| {
+            let cli = Cli::parse_from(["rpal"].iter().chain(args));
+            Session::build(cli, env::temp_dir()).unwrap().timeout_ms
+        };
+        let given = timeout(&["-t", "1500ms", "check", "tests/pal/check/success.c"]);
+        let not_given = timeout(&["check", "tests/pal/check/success.c"]);
+        assert_eq!(given, Some(1500));
+        assert_eq!(not_given, None);
+
+        // `--timeout` takes precedence over time limit of test config
+        let settings = ConfigSettings {
+            timeout: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(settings.timeout_ms(given), 1500);
+        assert_eq!(settings.timeout_ms(not_given), 2000);
+        assert_eq!(ConfigSettings::default().timeout_ms(given), 1500);
+        assert_eq!(ConfigSettings::default().timeout_ms(not_given), 10_000);
+    }
+
+    #[test]
+    fn test_find_job() {
+        let mut store = test_store();
+        assert_eq!(find_job(&mut store, 2).map(|(job, _, _)| job.id), Some(2));
+        assert_eq!(find_job(&mut store, 3).map(|(job, _, _)| job.id), Some(3));
+        assert!(find_job(&mut store, 4).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! Time and memory limits of tests, written with units like `1500ms` or `256MB`.
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Limits of running tested program, `None` if it's not limited by test config
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Time limit in milliseconds, written as seconds(`2`, `1.5`) or with unit(`1500ms`)
+    #[serde(deserialize_with = "time_limit")]
+    pub time: Option<u64>,
+    /// Memory limit in bytes, written as MB(`256`) or with unit(`512KB`, `1GB`)
+    #[serde(deserialize_with = "memory_limit")]
+    pub memory: Option<u64>,
+}
+
+impl Limits {
+    /// Limits of `self`, with those not set taken from `other`
+    pub fn or(self, other: Limits) -> Limits {
+        Limits {
+            time: self.time.or(other.time),
+            memory: self.memory.or(other.memory),
+        }
+    }
+}
+
+/// Time limit of std program, which is often a slow brute force in Pal tests
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StdLimit {
+    /// Time limit in milliseconds
+    Time(u64),
+    /// Multiple of time limit of tested program
+    Multiplier(f64),
+}
+
+impl Default for StdLimit {
+    fn default() -> StdLimit {
+        StdLimit::Multiplier(1.0)
+    }
+}
+
+impl StdLimit {
+    /// Limit given by `--std-timeout` or `--std-multiplier`, the same as tested program if
+    /// neither is given
+    pub fn from_args(std_timeout: Option<u64>, std_multiplier: Option<f64>) -> StdLimit {
+        match (std_timeout, std_multiplier) {
+            (Some(time), _) => StdLimit::Time(time),
+            (None, Some(multiplier)) => StdLimit::Multiplier(multiplier),
+            (None, None) => StdLimit::default(),
+        }
+    }
+
+    /// Time limit of std program in milliseconds, if tested program has `timeout_ms`
+    pub fn of(self, timeout_ms: u64) -> u64 {
+        match self {
+            Self::Time(time) => time,
+            Self::Multiplier(multiplier) => (timeout_ms as f64 * multiplier).round() as u64,
+        }
+    }
+
+    /// Time limit of std program in a test, where tested program has `time_limit` of the test
+    /// or else `timeout_ms` of all tests
+    pub fn of_test(self, time_limit: Option<u64>, timeout_ms: u64) -> u64 {
+        self.of(time_limit.unwrap_or(timeout_ms))
+    }
+}
+
+impl std::fmt::Display for StdLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Time(time) => write!(f, "{}", format_time(*time)),
+            Self::Multiplier(multiplier) => write!(f, "{}x", multiplier),
+        }
+    }
+}
+
+/// Parse multiplier like `3` or `2.5x`
+pub fn parse_multiplier(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    text.strip_suffix('x')
+        .unwrap_or(text)
+        .parse()
+        .ok()
+        .filter(|multiplier: &f64| multiplier.is_finite() && *multiplier > 0.0)
+        .ok_or_else(|| format!("Invalid multiplier: {}, should be like 3 or 2.5x", text))
+}
+
+/// Parse time like `1500ms`, `1.5s` or `2`(in seconds) into milliseconds
+pub fn parse_time(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let (number, scale) = match text.strip_suffix("ms") {
+        Some(number) => (number, 1.0),
+        None => (text.strip_suffix('s').unwrap_or(text), 1000.0),
+    };
+    parse_scaled(number, scale)
+        .ok_or_else(|| format!("Invalid time: {}, should be like 2, 1.5s or 1500ms", text))
+}
+
+/// Parse memory like `512KB`, `256MB`, `1GB` or `256`(in MB) into bytes, units are powers of
+/// 1024
+pub fn parse_memory(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let upper = text.to_ascii_uppercase();
+    let units = [
+        ("KB", 1u64 << 10),
+        ("MB", 1 << 20),
+        ("GB", 1 << 30),
+        ("K", 1 << 10),
+        ("M", 1 << 20),
+        ("G", 1 << 30),
+    ];
+    let (number, scale) = units
+        .iter()
+        .find_map(|(unit, scale)| upper.strip_suffix(unit).map(|number| (number, *scale)))
+        .unwrap_or((&upper, 1 << 20));
+    parse_scaled(number, scale as f64)
+        .ok_or_else(|| format!("Invalid memory: {}, should be like 256, 256MB or 1GB", text))
+}
+
+/// Time in milliseconds, shown in seconds if it's whole
+pub fn format_time(ms: u64) -> String {
+    if ms % 1000 == 0 {
+        format!("{}s", ms / 1000)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+/// Memory in bytes, shown in the largest unit it's a whole number of
+pub fn format_memory(bytes: u64) -> String {
+    match bytes {
+        0 => String::from("0B"),
+        _ if bytes % (1 << 30) == 0 => format!("{}GB", bytes >> 30),
+        _ if bytes % (1 << 20) == 0 => format!("{}MB", bytes >> 20),
+        _ if bytes % (1 << 10) == 0 => format!("{}KB", bytes >> 10),
+        _ => format!("{}B", bytes),
+    }
+}
+
+fn parse_scaled(number: &str, scale: f64) -> Option<u64> {
+    let number: f64 = number.trim().parse().ok()?;
+    (number.is_finite() && number > 0.0).then(|| (number * scale).round() as u64)
+}
+
+/// Limit written as a number or a string with unit
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Written {
+    Number(f64),
+    Text(String),
+}
+
+fn time_limit<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let text = match Written::deserialize(deserializer)? {
+        Written::Number(number) => number.to_string(),
+        Written::Text(text) => text,
+    };
+    parse_time(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn memory_limit<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let text = match Written::deserialize(deserializer)? {
+        Written::Number(number) => number.to_string(),
+        Written::Text(text) => text,
+    };
+    parse_memory(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_time("2"), Ok(2000));
+        assert_eq!(parse_time("1.5s"), Ok(1500));
+        assert_eq!(parse_time("1500ms"), Ok(1500));
+        assert!(parse_time("0").is_err());
+        assert!(parse_time("2min").is_err());
+
+        assert_eq!(parse_memory("256"), Ok(256 << 20));
+        assert_eq!(parse_memory("512KB"), Ok(512 << 10));
+        assert_eq!(parse_memory("1gb"), Ok(1 << 30));
+        assert_eq!(parse_memory("1.5G"), Ok(3 << 29));
+        assert!(parse_memory("lots").is_err());
+
+        assert_eq!(parse_multiplier("2.5x"), Ok(2.5));
+        assert!(parse_multiplier("-1").is_err());
+        assert_eq!(StdLimit::Multiplier(3.0).of(1500), 4500);
+        assert_eq!(StdLimit::Time(5000).of(1500), 5000);
+
+        assert_eq!(format_time(2000), "2s");
+        assert_eq!(format_time(1500), "1500ms");
+        assert_eq!(format_memory(256 << 20), "256MB");
+
+        let limits: Limits = toml::from_str("time = 1.5\nmemory = \"64MB\"\n").unwrap();
+        assert_eq!(
+            limits,
+            Limits {
+                time: Some(1500),
+                memory: Some(64 << 20),
+            }
+        );
+        assert!(toml::from_str::<Limits>("time = \"fast\"\n").is_err());
+    }
+
+    #[test]
+    fn test_std_limit_of_test() {
+        // Multiplier applies to time limit of the test, or else the one of all tests
+        let multiplier = StdLimit::from_args(None, Some(2.5));
+        assert_eq!(multiplier.of_test(Some(400), 1000), 1000);
+        assert_eq!(multiplier.of_test(None, 1000), 2500);
+
+        // Fixed time limit ignores both
+        let time = StdLimit::from_args(Some(3000), None);
+        assert_eq!(time, StdLimit::Time(3000));
+        assert_eq!(time.of_test(Some(400), 1000), 3000);
+        assert_eq!(time.of_test(None, 1000), 3000);
+
+        // Same as tested program by default
+        let default = StdLimit::from_args(None, None);
+        assert_eq!(default.of_test(Some(1500), 1000), 1500);
+        assert_eq!(default.of_test(None, 1000), 1000);
+    }
+}
@@ -1,24 +1,231 @@
-use crate::job::JobResult;
+use crate::job::{take_stray_processes, Comparator, FileIo, JobResult};
+use crate::limits::{format_time, StdLimit};
+use crate::problem::Subtask;
+use crate::session::{Session, DEFAULT_TIMEOUT_MS};
 use crate::threadpool::ThreadPool;
+use crate::ConfigSettings;
 use crate::{
-    job::{run_job, Job},
-    parser::parse,
+    job::{run_job, run_job_live, Job},
+    parser::{job_seed, random_pal_job, GlobLimit, ParseError, TestConfig},
 };
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs;
-use std::io::ErrorKind;
+use std::io::{self, ErrorKind, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 #[derive(Serialize, Deserialize)]
 pub struct PalStore {
     pub job_passed: Vec<(Job, JobResult, bool)>,
     pub job_failed: Vec<(Job, JobResult, bool)>,
     pub pal_info: PalInfo,
+    /// Master seed which seeds of jobs are derived from
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Results of subtasks defined in test config
+    #[serde(default)]
+    pub subtasks: Vec<SubtaskResult>,
+}
+
+/// Result of a subtask, which takes the lowest score of its tests and subtasks it depends on
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubtaskResult {
+    pub subtask: Subtask,
+    /// Points scored, from 0 to points of subtask
+    pub score: f64,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Results of jobs in a group of test config
+#[derive(Debug, PartialEq, Eq)]
+pub struct GroupResult {
+    /// `None` for jobs not in any group
+    pub name: Option<String>,
+    pub passed: usize,
+    pub failed: usize,
+    /// Number of failed jobs of each result, e.g. `("TLE", 3)`
+    pub failures: Vec<(String, usize)>,
+}
+
+impl PalStore {
+    /// Subtasks scored in results
+    pub fn subtask_list(&self) -> Vec<Subtask> {
+        self.subtasks
+            .iter()
+            .map(|result| result.subtask.clone())
+            .collect()
+    }
+
+    /// Score `subtasks` with results of jobs in groups of the same names
+    pub fn score_subtasks(&mut self, subtasks: &[Subtask]) {
+        let mut ratios: Vec<(&str, f64)> = Vec::new();
+        self.subtasks = subtasks
+            .iter()
+            .map(|subtask| {
+                let scores: Vec<f64> = self
+                    .job_passed
+                    .iter()
+                    .chain(&self.job_failed)
+                    .filter(|(job, _, _)| job.group.as_deref() == Some(subtask.name.as_str()))
+                    .map(|(_, job_result, _)| job_result.score())
+                    .collect();
+                let passed = scores.iter().filter(|score| **score == 1.0).count();
+                // Subtask without tests run isn't scored
+                let mut ratio = if scores.is_empty() {
+                    0.0
+                } else {
+                    scores.iter().copied().fold(1.0, f64::min)
+                };
+                for (name, depended) in &ratios {
+                    if subtask.depends.iter().any(|depend| depend == name) {
+                        ratio = ratio.min(*depended);
+                    }
+                }
+                ratios.push((&subtask.name, ratio));
+                SubtaskResult {
+                    subtask: subtask.clone(),
+                    score: subtask.points as f64 * ratio,
+                    passed,
+                    failed: scores.len() - passed,
+                }
+            })
+            .collect();
+    }
+
+    /// Results of each group, in order of their first jobs, empty if no job is grouped
+    pub fn groups(&self) -> Vec<GroupResult> {
+        let mut jobs: Vec<(&Job, Option<&JobResult>)> = self
+            .job_passed
+            .iter()
+            .map(|(job, _, _)| (job, None))
+            .chain(
+                self.job_failed
+                    .iter()
+                    .map(|(job, job_result, _)| (job, Some(job_result))),
+            )
+            .collect();
+        if jobs.iter().all(|(job, _)| job.group.is_none()) {
+            return Vec::new();
+        }
+        jobs.sort_by_key(|(job, _)| job.id);
+
+        let mut groups: Vec<GroupResult> = Vec::new();
+        for (job, failure) in jobs {
+            let index = match groups.iter().position(|group| group.name == job.group) {
+                Some(index) => index,
+                None => {
+                    groups.push(GroupResult {
+                        name: job.group.clone(),
+                        passed: 0,
+                        failed: 0,
+                        failures: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+            };
+            let group = &mut groups[index];
+            match failure {
+                None => group.passed += 1,
+                Some(job_result) => {
+                    group.failed += 1;
+                    // Details like those of OE are left out
+                    let kind = job_result.to_string();
+                    let kind = kind.split('(').next().unwrap_or_default().to_string();
+                    match group.failures.iter_mut().find(|(k, _)| *k == kind) {
+                        Some((_, count)) => *count += 1,
+                        None => group.failures.push((kind, 1)),
+                    }
+                }
+            }
+        }
+        groups
+    }
+}
+
+/// Print time limit of programs, files they use if tests use file I/O, and whether tested
+/// program runs in sandbox
+fn print_run_settings(pal_info: &PalInfo) {
+    match pal_info.std {
+        Some(_) => println!(
+            "Time limit: {}, std program: {}",
+            format_time(pal_info.timeout_ms),
+            pal_info.std_limit
+        ),
+        None => println!("Time limit: {}", format_time(pal_info.timeout_ms)),
+    }
+    if pal_info.sandbox {
+        println!("Running tested program in sandbox");
+    }
+    if let Some(file_io) = &pal_info.file_io {
+        println!(
+            "File I/O: input from {}, output to {}",
+            file_io.input, file_io.output
+        );
+    }
+}
+
+/// Print pass and fail count of each group
+pub fn print_groups(groups: &[GroupResult]) {
+    for group in groups {
+        let failures: Vec<String> = group
+            .failures
+            .iter()
+            .map(|(kind, count)| format!("{} x{}", kind, count))
+            .collect();
+        println!(
+            "{} {}: pass = {}, fail = {}{}",
+            if group.failed == 0 {
+                "PASSED"
+            } else {
+                "FAILED"
+            },
+            group.name.as_deref().unwrap_or("(no group)"),
+            group.passed,
+            group.failed,
+            if failures.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", failures.join(", "))
+            }
+        );
+    }
+}
+
+/// Print score of each subtask and the total score
+pub fn print_subtasks(subtasks: &[SubtaskResult]) {
+    if subtasks.is_empty() {
+        return;
+    }
+    println!(
+        "{:<16} {:>6} {:>6} {:>6} {:>6}  DEPENDS",
+        "SUBTASK", "POINTS", "SCORE", "PASS", "FAIL"
+    );
+    for result in subtasks {
+        let row = format!(
+            "{:<16} {:>6} {:>6} {:>6} {:>6}  {}",
+            result.subtask.name,
+            result.subtask.points,
+            format_score(result.score),
+            result.passed,
+            result.failed,
+            result.subtask.depends.join(", ")
+        );
+        println!("{}", row.trim_end());
+    }
+    let score: f64 = subtasks.iter().map(|result| result.score).sum();
+    let points: u32 = subtasks.iter().map(|result| result.subtask.points).sum();
+    println!("SCORE: {}/{}", format_score(score), points);
+}
+
+/// Score rounded to 2 decimal places
+fn format_score(score: f64) -> String {
+    ((score * 100.0).round() / 100.0).to_string()
 }
 
 #[derive(PartialEq, Eq)]
@@ -44,7 +251,36 @@ pub struct PalInfo {
     pub test_info_directory: String,
     pub job_store_filepath: String,
     pub std: Option<String>,
-    pub timeout_sec: u64,
+    /// Hash of the compiled std program binary
+    #[serde(default)]
+    pub std_hash: Option<String>,
+    /// Program to generate input of jobs with seed
+    #[serde(default)]
+    pub generator: Option<String>,
+    /// Program to judge output of user program, instead of comparing it with expected output
+    #[serde(default)]
+    pub checker: Option<String>,
+    #[serde(default)]
+    pub comparator: Comparator,
+    /// Time limit of programs in milliseconds, unless tested program has its own in job
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Time limit of std program, relative to the one of tested program
+    #[serde(default)]
+    pub std_limit: StdLimit,
+    /// Files programs read and write instead of stdin and stdout
+    #[serde(default)]
+    pub file_io: Option<FileIo>,
+    /// Files and directories copied to working directory of every job
+    #[serde(default)]
+    pub fixtures: Vec<String>,
+    /// Run tested program in sandbox
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
 }
 
 pub struct CompileConfig {
@@ -52,9 +288,35 @@ pub struct CompileConfig {
     pub args: String,
     pub source: String,
     pub std_source: Option<String>,
+    pub generator_source: Option<String>,
+    pub checker_source: Option<String>,
+    /// How output is compared with expected output, if there is no checker
+    pub comparator: Comparator,
+    pub std_limit: StdLimit,
+    pub file_io: Option<FileIo>,
+    pub fixtures: Vec<String>,
+    /// Run tested program in sandbox, see `sandbox`
+    pub sandbox: bool,
     pub work_directory: String,
 }
 
+/// Earlier results a retest keeps in the store it saves
+#[derive(Default)]
+pub struct RetestBase {
+    /// Passed jobs which aren't retested, scored in subtasks and groups with retested jobs
+    pub job_passed: Vec<(Job, JobResult, bool)>,
+    pub subtasks: Vec<Subtask>,
+    /// Master seed of stored jobs, kept so they can be reproduced with `--seed`
+    pub seed: Option<u64>,
+}
+
+pub struct StressConfig {
+    pub batch_size: usize,
+    pub max_tests: Option<usize>,
+    pub max_time: Option<Duration>,
+    pub max_failures: usize,
+}
+
 impl std::fmt::Display for PalType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -79,6 +341,33 @@ impl std::fmt::Debug for PalError {
 }
 
 impl CompileConfig {
+    /// Config to compile programs of session with settings of its test config, including std
+    /// program for Pal and RandomPal, and generator program for Pal
+    pub(crate) fn from_session(session: &Session, settings: &ConfigSettings) -> Self {
+        let std_source = match session.pal_type {
+            PalType::Pal | PalType::RandomPal => Some(session.std_source.clone()),
+            _ => None,
+        };
+        let generator_source = match session.pal_type {
+            PalType::Pal => settings.generator_source.clone(),
+            _ => None,
+        };
+        CompileConfig {
+            compiler: session.compiler.clone(),
+            args: session.compiler_arguments.clone(),
+            source: session.source.clone(),
+            std_source,
+            generator_source,
+            checker_source: settings.checker_source.clone(),
+            comparator: settings.comparator,
+            std_limit: session.std_limit,
+            file_io: settings.file_io.clone(),
+            fixtures: settings.fixtures.clone(),
+            sandbox: session.sandbox,
+            work_directory: session.current_working_directory.clone(),
+        }
+    }
+
     pub fn command(&self) -> String {
         format!("{} {}", self.compiler, self.args)
     }
@@ -86,7 +375,7 @@ impl CompileConfig {
 
 pub fn compile(
     compile_config: CompileConfig,
-    timeout_sec: u64,
+    timeout_ms: u64,
     job_store_path: &str,
 ) -> Result<PalInfo, String> {
     let work_directory_path = PathBuf::from_str(&compile_config.work_directory)
@@ -130,12 +419,102 @@ pub fn compile(
     let output = output_dir.join(source_prefix).to_str().unwrap().to_string();
 
     // Compile user program
+    compile_program(
+        &compile_config,
+        &work_directory_path,
+        &source,
+        &output,
+        "user program",
+    )?;
+
+    // Compile std program
+    let (std_output, std_hash) = match &compile_config.std_source {
+        Some(std_source) => {
+            let std_output = build_output_path(&output_dir, std_source)?;
+            let std_source = work_directory_path
+                .join(std_source)
+                .to_str()
+                .unwrap()
+                .to_string();
+            compile_program(
+                &compile_config,
+                &work_directory_path,
+                &std_source,
+                &std_output,
+                "std program",
+            )?;
+            let std_hash = file_hash(&std_output)?;
+            (Some(std_output), Some(std_hash))
+        }
+        None => (None, None),
+    };
+
+    // Compile generator and checker program
+    let compile_helper = |source: &Option<String>, name: &str| -> Result<Option<String>, String> {
+        match source {
+            Some(source) => {
+                let output = build_output_path(&output_dir, source)?;
+                let source = work_directory_path
+                    .join(source)
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                compile_program(
+                    &compile_config,
+                    &work_directory_path,
+                    &source,
+                    &output,
+                    name,
+                )?;
+                Ok(Some(output))
+            }
+            None => Ok(None),
+        }
+    };
+    let generator_output = compile_helper(&compile_config.generator_source, "generator program")?;
+    let checker_output = compile_helper(&compile_config.checker_source, "checker program")?;
+
+    Ok(PalInfo {
+        prog: output,
+        work_directory: String::from(work_directory_path.to_str().unwrap()),
+        out_directory: String::from(output_dir.to_str().unwrap()),
+        test_info_directory: String::from(test_info_dir.to_str().unwrap()),
+        job_store_filepath: job_store_path.to_string(),
+        std: std_output,
+        std_hash,
+        generator: generator_output,
+        checker: checker_output,
+        comparator: compile_config.comparator,
+        timeout_ms,
+        std_limit: compile_config.std_limit,
+        file_io: compile_config.file_io,
+        fixtures: compile_config.fixtures,
+        sandbox: compile_config.sandbox,
+    })
+}
+
+fn build_output_path(output_dir: &Path, source: &str) -> Result<String, String> {
+    let source_prefix = Path::new(source)
+        .file_stem()
+        .ok_or_else(|| format!("Invalid source filename: {}", source))?
+        .to_str()
+        .unwrap();
+    Ok(output_dir.join(source_prefix).to_str().unwrap().to_string())
+}
+
+fn compile_program(
+    compile_config: &CompileConfig,
+    work_directory_path: &Path,
+    source: &str,
+    output: &str,
+    name: &str,
+) -> Result<(), String> {
     let mut args: Vec<&str> = compile_config.args.split(" ").collect();
-    args.push(source.as_str());
+    args.push(source);
     args.push("-o");
-    args.push(output.as_str());
+    args.push(output);
 
-    let p = Command::new(compile_config.compiler.clone())
+    let p = Command::new(&compile_config.compiler)
         .args(args)
         .current_dir(work_directory_path.to_str().unwrap())
         .output()
@@ -143,100 +522,61 @@ pub fn compile(
 
     if !p.status.success() {
         return Err(format!(
-            "user program compile failed: \n{}{}",
+            "{} compile failed: \n{}{}",
+            name,
             String::from_utf8(p.stdout).unwrap(),
             String::from_utf8(p.stderr).unwrap(),
         ));
     }
 
-    match &compile_config.std_source {
-        Some(std_source) => {
-            let std_source_prefix = Path::new(&std_source)
-                .file_stem()
-                .ok_or_else(|| format!("Invalid source filename: {}", std_source))?
-                .to_str()
-                .unwrap();
-            let std_source = work_directory_path
-                .join(&std_source)
-                .to_str()
-                .unwrap()
-                .to_string();
-            let std_output = output_dir
-                .join(std_source_prefix)
-                .to_str()
-                .unwrap()
-                .to_string();
-            // Compile std program
-            let mut args: Vec<&str> = compile_config.args.split(" ").collect();
-            args.push(std_source.as_str());
-            args.push("-o");
-            args.push(std_output.as_str());
-
-            let p = Command::new(compile_config.compiler)
-                .args(args)
-                .current_dir(work_directory_path.to_str().unwrap())
-                .output()
-                .map_err(|e| format!("Failed to launch compiler: {}", e))?;
-
-            if p.status.success() {
-                Ok(PalInfo {
-                    prog: String::from(output),
-                    work_directory: String::from(work_directory_path.to_str().unwrap()),
-                    out_directory: String::from(output_dir.to_str().unwrap()),
-                    test_info_directory: String::from(test_info_dir.to_str().unwrap()),
-                    job_store_filepath: job_store_path.to_string(),
-                    std: Some(String::from(std_output)),
-                    timeout_sec,
-                })
-            } else {
-                Err(format!(
-                    "std program compile failed: \n{}{}",
-                    String::from_utf8(p.stdout).unwrap(),
-                    String::from_utf8(p.stderr).unwrap(),
-                ))
-            }
-        }
-
-        None => Ok(PalInfo {
-            prog: String::from(output),
-            work_directory: String::from(work_directory_path.to_str().unwrap()),
-            out_directory: String::from(output_dir.to_str().unwrap()),
-            test_info_directory: String::from(test_info_dir.to_str().unwrap()),
-            job_store_filepath: job_store_path.to_string(),
-            std: None,
-            timeout_sec,
-        }),
-    }
+    Ok(())
 }
 
 pub fn run_pal(
     pal_type: PalType,
     compile_config: CompileConfig,
-    test_config: &str,
+    test_config: &TestConfig,
     job_store_path: String,
-    timeout_sec: u64,
+    timeout_ms: u64,
+    seed: u64,
+    glob_limit: GlobLimit,
 ) -> Result<(), PalError> {
     let now = Instant::now();
     println!("Running for type: {}", pal_type);
     println!("Parsing config...");
-    let job_list =
-        parse(&pal_type, test_config).map_err(|e| PalError::ParseError(format!("{:?}", e)))?;
+    let test_config_str = test_config.read().map_err(PalError::IOError)?;
+    let (job_count, mut job_iter) = test_config
+        .parse_lazy(&pal_type, &test_config_str, seed, glob_limit)
+        .map_err(|e| PalError::ParseError(format!("{:?}", test_config.locate(e))))?;
+    let subtasks = test_config
+        .subtasks(&test_config_str)
+        .map_err(|e| PalError::ParseError(format!("{:?}", test_config.locate(e))))?;
 
     let parse_time = now.elapsed().as_millis();
 
     let now = Instant::now();
 
-    println!("Job count: {}", job_list.len());
-    let thread_count = job_list
-        .len()
-        .min(std::thread::available_parallelism().unwrap().into());
+    println!("Job count: {}", job_count);
+    if let PalType::RandomPal = pal_type {
+        // Config is parsed above, so counting won't fail
+        for (label, count) in test_config
+            .count_blocks(&pal_type, &test_config_str, u128::MAX)
+            .unwrap_or_default()
+        {
+            println!("Input at {}: {} tests", label, count);
+        }
+    }
+    let thread_count = job_count
+        .min(std::thread::available_parallelism().unwrap().into())
+        .max(1);
 
     let mut passed = 0;
     let mut failed = 0;
 
     println!("Compiling using: {}", compile_config.command());
-    let pal_info = compile(compile_config, timeout_sec, &job_store_path)
-        .map_err(|e| PalError::CompileError(e))?;
+    let pal_info =
+        compile(compile_config, timeout_ms, &job_store_path).map_err(PalError::CompileError)?;
+    print_run_settings(&pal_info);
 
     let compile_time = now.elapsed().as_millis();
     let now = Instant::now();
@@ -245,70 +585,113 @@ pub fn run_pal(
     println!("Test info directory: {}", &pal_info.test_info_directory);
     println!("A \".\" indicates a passed test. A \"X\" indicates a failed test: ");
 
-    let mut pool = ThreadPool::new(thread_count);
+    let pool = ThreadPool::new(thread_count);
 
     let (tx, rx) = mpsc::channel();
 
     let pal_type_arc = Arc::new(pal_type);
     let pal_info_arc = Arc::new(pal_info.clone());
 
-    for job in job_list {
-        let this_tx = tx.clone();
-        let this_pal_type = Arc::clone(&pal_type_arc);
-        let this_pal_info = Arc::clone(&pal_info_arc);
-        pool.execute(move || {
-            let result = run_job(this_pal_type, this_pal_info, job);
-
-            this_tx.send(result).unwrap();
-        });
-    }
-
-    drop(tx);
-
     let mut job_passed = Vec::new();
     let mut job_failed = Vec::new();
 
-    for (job, job_result) in rx {
+    let mut handle_result = |(job, job_result): (Job, JobResult)| {
         if !job_result.is_passed() {
             failed += 1;
             print!("X");
             job_failed.push((job, job_result, false));
-
-            pool.shutdown();
         } else {
             passed += 1;
             print!(".");
             job_passed.push((job, job_result, false));
         }
+    };
+
+    // Jobs are generated while running, keep a few of them queued for each thread
+    let max_in_flight = thread_count * 4;
+    let mut in_flight = 0;
+    let mut parse_error = None;
+
+    for job in job_iter.by_ref() {
+        let job = match job {
+            Ok(job) => job,
+            Err(e) => {
+                parse_error = Some(e);
+                break;
+            }
+        };
+        if in_flight >= max_in_flight {
+            handle_result(rx.recv().unwrap());
+            in_flight -= 1;
+        }
+
+        let this_tx = tx.clone();
+        let this_pal_type = Arc::clone(&pal_type_arc);
+        let this_pal_info = Arc::clone(&pal_info_arc);
+        pool.execute(move || {
+            let result = run_job(this_pal_type, this_pal_info, job);
+
+            this_tx.send(result).unwrap();
+        });
+        in_flight += 1;
+    }
+
+    drop(tx);
+
+    for result in rx {
+        handle_result(result);
     }
 
     drop(pool);
 
     println!();
+    print_failed_names(&job_failed);
 
-    let store = PalStore {
+    if let Some(e) = parse_error {
+        return Err(PalError::ParseError(format!("{:?}", test_config.locate(e))));
+    }
+
+    let mut store = PalStore {
         job_passed,
         job_failed,
         pal_info,
+        seed: Some(seed),
+        subtasks: Vec::new(),
     };
+    store.score_subtasks(&subtasks);
 
     let run_time = now.elapsed().as_millis();
 
+    let groups = store.groups();
+    let subtask_results = store.subtasks.clone();
     save_pal(&job_store_path, store)?;
 
-    summarize(([passed, failed], [parse_time, compile_time, run_time]));
+    print_groups(&groups);
+    summarize(
+        ([passed, failed], [parse_time, compile_time, run_time]),
+        &subtask_results,
+    );
 
     Ok(())
 }
 
+/// Retest given jobs. With `PalType::Retest`, jobs are checked against their stored expected
+/// output, with `PalType::Pal` or `PalType::RandomPal` expected output is regenerated by std program.
+/// Results are reported in order of `job_list`.
 pub fn run_retest(
+    pal_type: PalType,
     compile_config: CompileConfig,
     job_list: Vec<Job>,
-    job_store_path: &String,
-    timeout_sec: u64,
+    base: RetestBase,
+    job_store_path: &str,
+    timeout_ms: u64,
 ) -> Result<(), PalError> {
     let now = Instant::now();
     println!("Retesting...");
+    match pal_type {
+        PalType::Retest => {}
+        _ => println!("Regenerating expected output using std program..."),
+    }
 
     let parse_time = now.elapsed().as_millis();
 
@@ -317,14 +700,16 @@ pub fn run_retest(
     println!("Job count: {}", job_list.len());
     let thread_count = job_list
         .len()
-        .min(std::thread::available_parallelism().unwrap().into());
+        .min(std::thread::available_parallelism().unwrap().into())
+        .max(1);
 
     let mut passed = 0;
     let mut failed = 0;
 
     println!("Compiling using: {}", compile_config.command());
-    let pal_info = compile(compile_config, timeout_sec, &job_store_path)
-        .map_err(|e| PalError::CompileError(e))?;
+    let pal_info =
+        compile(compile_config, timeout_ms, job_store_path).map_err(PalError::CompileError)?;
+    print_run_settings(&pal_info);
 
     let compile_time = now.elapsed().as_millis();
     let now = Instant::now();
@@ -336,17 +721,18 @@ pub fn run_retest(
 
     let (tx, rx) = mpsc::channel();
 
-    let pal_type_arc = Arc::new(PalType::Retest);
+    let pal_type_arc = Arc::new(pal_type);
     let pal_info_arc = Arc::new(pal_info.clone());
 
-    for job in job_list {
+    let job_count = job_list.len();
+    for (index, job) in job_list.into_iter().enumerate() {
         let this_tx = tx.clone();
         let this_pal_type = Arc::clone(&pal_type_arc);
         let this_pal_info = Arc::clone(&pal_info_arc);
         pool.execute(move || {
             let result = run_job(this_pal_type, this_pal_info, job);
 
-            this_tx.send(result).unwrap();
+            this_tx.send((index, result)).unwrap();
         });
     }
 
@@ -355,39 +741,239 @@ pub fn run_retest(
     let mut job_passed = Vec::new();
     let mut job_failed = Vec::new();
 
-    for (job, job_result) in rx {
+    let mut handle_result = |(job, job_result): (Job, JobResult)| {
         if !job_result.is_passed() {
             failed += 1;
             print!("X");
             job_failed.push((job, job_result, false));
-
-            pool.shutdown();
         } else {
             passed += 1;
             print!(".");
             job_passed.push((job, job_result, false));
         }
+    };
+
+    // Results are reported in order of job list, e.g. previously failed tests first, those
+    // finished early wait for the ones before them
+    let mut results: Vec<Option<(Job, JobResult)>> = (0..job_count).map(|_| None).collect();
+    let mut next = 0;
+    for (index, result) in rx {
+        if !result.1.is_passed() {
+            pool.shutdown();
+        }
+        results[index] = Some(result);
+        while let Some(result) = results.get_mut(next).and_then(Option::take) {
+            handle_result(result);
+            next += 1;
+        }
     }
 
     drop(pool);
 
     println!();
+    print_failed_names(&job_failed);
 
-    let store = PalStore {
+    job_passed.extend(base.job_passed);
+    let mut store = PalStore {
         job_passed,
         job_failed,
         pal_info,
+        seed: base.seed,
+        subtasks: Vec::new(),
+    };
+    store.score_subtasks(&base.subtasks);
+
+    let run_time = now.elapsed().as_millis();
+
+    let groups = store.groups();
+    let subtask_results = store.subtasks.clone();
+    save_pal(job_store_path, store)?;
+
+    print_groups(&groups);
+    summarize(
+        ([passed, failed], [parse_time, compile_time, run_time]),
+        &subtask_results,
+    );
+
+    Ok(())
+}
+
+/// Keep running random generated tests in batches, until enough failures are found or
+/// test count/time budget is exhausted. Only failed tests are saved.
+pub fn run_stress(
+    compile_config: CompileConfig,
+    test_config: &TestConfig,
+    job_store_path: String,
+    timeout_ms: u64,
+    seed: u64,
+    stress_config: StressConfig,
+) -> Result<(), PalError> {
+    let now = Instant::now();
+    println!("Running stress test...");
+    println!("Parsing config...");
+    let test_config_str = test_config.read().map_err(PalError::IOError)?;
+    let parse_error = |e: ParseError| PalError::ParseError(format!("{:?}", test_config.locate(e)));
+    let blocks = test_config
+        .random_pal_inputs(&test_config_str)
+        .map_err(parse_error)?;
+    // Check directives before running, so errors won't show up as failed tests
+    for (block, _, _) in &blocks {
+        block.check().map_err(parse_error)?;
+    }
+    if blocks.iter().all(|(_, _, count)| *count == 0) {
+        return Err(PalError::ParseError(String::from(
+            "No test to run, number of tests should be positive",
+        )));
+    }
+
+    let parse_time = now.elapsed().as_millis();
+    let now = Instant::now();
+
+    println!("Compiling using: {}", compile_config.command());
+    let pal_info =
+        compile(compile_config, timeout_ms, &job_store_path).map_err(PalError::CompileError)?;
+    print_run_settings(&pal_info);
+
+    let compile_time = now.elapsed().as_millis();
+    let now = Instant::now();
+
+    let thread_count: usize = std::thread::available_parallelism().unwrap().into();
+    println!(
+        "Running jobs in batches of {} using {} threads...",
+        stress_config.batch_size, thread_count
+    );
+    println!("Test info directory: {}", &pal_info.test_info_directory);
+
+    let pool = ThreadPool::new(thread_count);
+
+    let pal_type_arc = Arc::new(PalType::RandomPal);
+    let pal_info_arc = Arc::new(pal_info.clone());
+    let blocks_arc = Arc::new(blocks);
+    // Set once a limit is reached, so queued jobs of the batch are skipped
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let mut passed = 0;
+    let mut job_failed = Vec::new();
+    let mut next_id = 0;
+
+    loop {
+        let batch_size = match stress_config.max_tests {
+            Some(max_tests) => stress_config.batch_size.min(max_tests - next_id),
+            None => stress_config.batch_size,
+        };
+
+        let (tx, rx) = mpsc::channel();
+        for id in next_id..next_id + batch_size {
+            let this_tx = tx.clone();
+            let this_pal_type = Arc::clone(&pal_type_arc);
+            let this_pal_info = Arc::clone(&pal_info_arc);
+            let this_blocks = Arc::clone(&blocks_arc);
+            let this_stop = Arc::clone(&stop);
+            pool.execute(move || {
+                if this_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Generate input in worker thread, as expanding could be slow. It's generated
+                // like `rpal random-pal --only`, so failed tests can be reproduced with it
+                let result = match random_pal_job(&this_blocks, seed, id) {
+                    Ok(job) => run_job(this_pal_type, this_pal_info, job),
+                    Err(e) => (
+                        Job {
+                            id,
+                            seed: Some(job_seed(seed, id)),
+                            ..Default::default()
+                        },
+                        JobResult::OtherError(format!("{:?}", e)),
+                    ),
+                };
+
+                this_tx.send(result).unwrap();
+            });
+        }
+        drop(tx);
+        next_id += batch_size;
+
+        // Limits are checked after every job, not only between batches
+        for (job, job_result) in rx {
+            if job_result.is_passed() {
+                passed += 1;
+            } else {
+                job_failed.push((job, job_result, false));
+            }
+            if job_failed.len() >= stress_config.max_failures
+                || stress_config
+                    .max_tests
+                    .is_some_and(|max_tests| passed + job_failed.len() >= max_tests)
+                || stress_config
+                    .max_time
+                    .is_some_and(|max_time| now.elapsed() >= max_time)
+            {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let tests = passed + job_failed.len();
+        print!(
+            "\rtests: {}, failed: {}, {:.1} tests/s",
+            tests,
+            job_failed.len(),
+            tests as f64 / now.elapsed().as_secs_f64()
+        );
+        io::stdout().flush().unwrap();
+
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+
+    drop(pool);
+
+    println!();
+
+    let failed = job_failed.len();
+    job_failed.sort_by_key(|(job, _, _)| job.id);
+    job_failed.truncate(stress_config.max_failures);
+
+    let store = PalStore {
+        job_passed: Vec::new(),
+        job_failed,
+        pal_info,
+        seed: Some(seed),
+        subtasks: Vec::new(),
     };
 
     let run_time = now.elapsed().as_millis();
 
     save_pal(&job_store_path, store)?;
 
-    summarize(([passed, failed], [parse_time, compile_time, run_time]));
+    summarize(
+        ([passed, failed], [parse_time, compile_time, run_time]),
+        &[],
+    );
 
     Ok(())
 }
 
+pub fn run_single(
+    pal_type: PalType,
+    compile_config: CompileConfig,
+    job: Job,
+    job_store_path: &str,
+    timeout_ms: u64,
+) -> Result<JobResult, PalError> {
+    println!("Compiling using: {}", compile_config.command());
+    let pal_info =
+        compile(compile_config, timeout_ms, job_store_path).map_err(PalError::CompileError)?;
+
+    println!("Running job {}...", job.label());
+    let (job, job_result) = run_job_live(&pal_type, &pal_info, job);
+
+    println!("Result: {}", crate::result_name(&job_result));
+    println!("time: {}", job.limits_report(pal_info.timeout_ms));
+
+    Ok(job_result)
+}
+
 fn save_pal(job_store_path: &str, store: PalStore) -> Result<(), PalError> {
     let store_path = Path::new(job_store_path);
     println!("Saving test result to {}...", store_path.to_str().unwrap());
@@ -397,14 +983,37 @@ fn save_pal(job_store_path: &str, store: PalStore) -> Result<(), PalError> {
     Ok(())
 }
 
-fn summarize(info: ([usize; 2], [u128; 3])) {
+/// List failed tests which have names, e.g. read from test data files
+fn print_failed_names(job_failed: &[(Job, JobResult, bool)]) {
+    let mut failed: Vec<&Job> = job_failed
+        .iter()
+        .map(|(job, _, _)| job)
+        .filter(|job| job.name.is_some() || job.group.is_some())
+        .collect();
+    if failed.is_empty() {
+        return;
+    }
+    failed.sort_by_key(|job| job.id);
+    let names: Vec<String> = failed.iter().filter_map(|job| job.full_name()).collect();
+    println!("Failed: {}", names.join(", "));
+}
+
+fn summarize(info: ([usize; 2], [u128; 3]), subtasks: &[SubtaskResult]) {
     let ([passed, failed], [parse_time, compile_time, run_time]) = info;
+    print_subtasks(subtasks);
     if failed == 0 {
         println!("PASSED: pass = {}, fail = {}", passed, failed);
     } else {
         println!("FAILED: pass = {}, fail = {}", passed, failed);
     }
 
+    let stray_processes = take_stray_processes();
+    if stray_processes > 0 {
+        println!(
+            "Killed {} stray processes left by timed out programs",
+            stray_processes
+        );
+    }
     println!(
         "time: {}ms(total) = {}ms(parse) + {}ms(compile) + {}ms(run)",
         parse_time + compile_time + run_time,
@@ -414,11 +1023,54 @@ fn summarize(info: ([usize; 2], [u128; 3])) {
     );
 }
 
+/// FNV-1a hash of file content, in hex
+fn file_hash(path: &str) -> Result<String, String> {
+    let content = fs::read(path).map_err(|e| format!("Cannot read {}: {:?}", path, e))?;
+    let hash = content.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    Ok(format!("{:016x}", hash))
+}
+
 pub fn parse_store(json_content: &str) -> Result<PalStore, PalError> {
-    let store = serde_json::from_str(json_content)
-        .map_err(|e| PalError::LoadStoreError(format!("Failed parse store file: {:?}", e)))?;
+    let parse_error = |e: serde_json::Error| {
+        PalError::LoadStoreError(format!("Failed parse store file: {:?}", e))
+    };
+    let mut store = serde_json::from_str(json_content).map_err(parse_error)?;
+    migrate_store(&mut store);
 
-    Ok(store)
+    serde_json::from_value(store).map_err(parse_error)
+}
+
+/// Store files written before time limits were in milliseconds have `timeout_sec` in
+/// `pal_info`, and std or generator program timed out with time limit in seconds
+fn migrate_store(store: &mut serde_json::Value) {
+    let seconds_to_ms =
+        |seconds: &serde_json::Value| serde_json::Value::from(seconds.as_u64().map(|s| s * 1000));
+    let timeout_sec = store
+        .get_mut("pal_info")
+        .and_then(|pal_info| pal_info.as_object_mut())
+        .and_then(|pal_info| pal_info.remove("timeout_sec"));
+    let timeout_sec = match timeout_sec {
+        Some(timeout_sec) => timeout_sec,
+        None => return,
+    };
+    store["pal_info"]["timeout_ms"] = seconds_to_ms(&timeout_sec);
+
+    for jobs in ["job_passed", "job_failed"] {
+        let results = store[jobs]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.get_mut(1));
+        for result in results {
+            for error in ["/StdProgramError/TimeOut", "/GeneratorError/TimeOut"] {
+                if let Some(timeout) = result.pointer_mut(error) {
+                    *timeout = seconds_to_ms(timeout);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -427,6 +1079,128 @@ mod tests {
     use std::env;
     use std::fs;
     use std::path::Path;
+
+    /// Config compiling programs with gcc, without generator or checker program
+    fn gcc_config(
+        source: String,
+        std_source: Option<String>,
+        work_directory: String,
+    ) -> CompileConfig {
+        CompileConfig {
+            compiler: String::from("gcc"),
+            args: String::from("-Wall -Wextra -lm"),
+            source,
+            std_source,
+            generator_source: None,
+            checker_source: None,
+            comparator: Comparator::default(),
+            std_limit: StdLimit::default(),
+            file_io: None,
+            fixtures: Vec::new(),
+            sandbox: false,
+            work_directory,
+        }
+    }
+
+    #[test]
+    fn test_score_subtasks() {
+        let job = |id: usize, group: &str| Job {
+            id,
+            group: Some(group.to_string()),
+            ..Default::default()
+        };
+        let subtask = |name: &str, points: u32, depends: &[&str]| Subtask {
+            name: name.to_string(),
+            points,
+            depends: depends.iter().map(|name| name.to_string()).collect(),
+        };
+        let mut store = PalStore {
+            job_passed: vec![
+                (job(0, "small"), JobResult::Accepted, false),
+                (job(1, "small"), JobResult::Accepted, false),
+                (job(3, "large"), JobResult::Accepted, false),
+            ],
+            job_failed: vec![
+                (job(2, "medium"), JobResult::PartiallyCorrect(0.5), false),
+                (job(4, "large"), JobResult::TimeLimitExceed, false),
+            ],
+            pal_info: PalInfo {
+                prog: String::new(),
+                work_directory: String::new(),
+                out_directory: String::new(),
+                test_info_directory: String::new(),
+                job_store_filepath: String::new(),
+                std: None,
+                std_hash: None,
+                generator: None,
+                checker: None,
+                comparator: Comparator::default(),
+                std_limit: StdLimit::default(),
+                file_io: None,
+                fixtures: Vec::new(),
+                sandbox: false,
+                timeout_ms: 1,
+            },
+            seed: None,
+            subtasks: Vec::new(),
+        };
+        store.score_subtasks(&[
+            subtask("small", 20, &[]),
+            subtask("medium", 30, &["small"]),
+            subtask("extra", 10, &["small"]),
+            subtask("large", 40, &["medium"]),
+        ]);
+        let scores: Vec<(f64, usize, usize)> = store
+            .subtasks
+            .iter()
+            .map(|result| (result.score, result.passed, result.failed))
+            .collect();
+        assert_eq!(
+            scores,
+            [(20.0, 2, 0), (15.0, 0, 1), (0.0, 0, 0), (0.0, 1, 1)]
+        );
+        assert_eq!(store.subtask_list()[3], subtask("large", 40, &["medium"]));
+    }
+
+    #[test]
+    fn test_parse_old_store() {
+        // Written when time limits were in seconds
+        let store = parse_store(
+            r#"{
+                "job_passed": [],
+                "job_failed": [
+                    [{"id": 0, "input": [], "expected_output": [], "actual_output": []},
+                        {"StdProgramError": {"TimeOut": 2}}, false],
+                    [{"id": 1, "input": [], "expected_output": [], "actual_output": []},
+                        {"GeneratorError": {"TimeOut": 2}}, false],
+                    [{"id": 2, "input": [], "expected_output": [], "actual_output": []},
+                        "WrongAnswer", false]
+                ],
+                "pal_info": {"prog": "", "work_directory": "", "out_directory": "",
+                    "test_info_directory": "", "job_store_filepath": "", "std": null,
+                    "timeout_sec": 2}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(store.pal_info.timeout_ms, 2000);
+        let results: Vec<String> = store
+            .job_failed
+            .iter()
+            .map(|(_, job_result, _)| job_result.to_string())
+            .collect();
+        assert!(results[0].starts_with("STDERR") && results[0].contains("2000ms"));
+        assert!(results[1].starts_with("GENERR") && results[1].contains("2000ms"));
+        assert_eq!(results[2], "WA");
+
+        let store = parse_store(
+            r#"{"job_passed": [], "job_failed": [], "pal_info": {"prog": "",
+                "work_directory": "", "out_directory": "", "test_info_directory": "",
+                "job_store_filepath": "", "std": null}}"#,
+        )
+        .unwrap();
+        assert_eq!(store.pal_info.timeout_ms, DEFAULT_TIMEOUT_MS);
+    }
+
     #[test]
     fn test_compile_check_success() {
         let work_dir = std::env::current_dir().unwrap();
@@ -437,16 +1211,9 @@ mod tests {
             .to_str()
             .unwrap()
             .to_string();
-        let args = String::from("-Wall -lm -Wextra");
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args,
-            source,
-            std_source: None,
-            work_directory: work_dir.to_str().unwrap().to_string(),
-        };
+        let compile_config = gcc_config(source, None, work_dir.to_str().unwrap().to_string());
         let job_store_path = work_dir.join("tests/compile/tests_info/success.json");
-        let pal_info = compile(compile_config, 10, job_store_path.to_str().unwrap()).unwrap();
+        let pal_info = compile(compile_config, 10000, job_store_path.to_str().unwrap()).unwrap();
 
         assert!(Path::new(&pal_info.prog).exists());
 
@@ -466,16 +1233,9 @@ mod tests {
             .to_str()
             .unwrap()
             .to_string();
-        let args = String::from("-Wall -lm -Wextra");
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args,
-            source,
-            std_source: None,
-            work_directory: work_dir.to_str().unwrap().to_string(),
-        };
+        let compile_config = gcc_config(source, None, work_dir.to_str().unwrap().to_string());
         let job_store_path = work_dir.join("tests/compile/tests_info/failed.json");
-        let pal_info = compile(compile_config, 10, job_store_path.to_str().unwrap());
+        let pal_info = compile(compile_config, 10000, job_store_path.to_str().unwrap());
 
         assert!(pal_info.is_err());
     }
@@ -499,16 +1259,13 @@ mod tests {
             .to_str()
             .unwrap()
             .to_string();
-        let args = String::from("-Wall -lm -Wextra");
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args,
+        let compile_config = gcc_config(
             source,
-            std_source: Some(std_source),
-            work_directory: work_dir.to_str().unwrap().to_string(),
-        };
+            Some(std_source),
+            work_dir.to_str().unwrap().to_string(),
+        );
         let job_store_path = work_dir.join("tests/compile/pal/tests_info/success.json");
-        let pal_info = compile(compile_config, 10, job_store_path.to_str().unwrap()).unwrap();
+        let pal_info = compile(compile_config, 10000, job_store_path.to_str().unwrap()).unwrap();
         let std_prog = pal_info.std.unwrap();
         assert!(Path::new(&pal_info.prog).exists());
         assert!(Path::new(&std_prog).exists());
@@ -538,26 +1295,21 @@ mod tests {
             .to_str()
             .unwrap()
             .to_string();
-        let args = String::from("-Wall -lm -Wextra");
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args,
+        let compile_config = gcc_config(
             source,
-            std_source: Some(std_source),
-            work_directory: work_dir.to_str().unwrap().to_string(),
-        };
+            Some(std_source),
+            work_dir.to_str().unwrap().to_string(),
+        );
         let job_store_path = work_dir.join("tests/compile/pal/tests_info/user_ce.json");
-        let result = compile(compile_config, 10, job_store_path.to_str().unwrap());
+        let result = compile(compile_config, 10000, job_store_path.to_str().unwrap());
         assert!(result.is_err());
     }
 
     #[test]
     fn test_run_pal_check_success() {
         let cwd = env::current_dir().unwrap();
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args: String::from("-Wall -Wextra -lm"),
-            source: String::from(
+        let compile_config = gcc_config(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("check")
@@ -565,28 +1317,306 @@ mod tests {
                     .to_str()
                     .unwrap(),
             ),
-            std_source: None,
-            work_directory: String::from(cwd.join("tests").join("pal").to_str().unwrap()),
-        };
-        let test_config = fs::read_to_string("tests/pal/check/success.test").unwrap();
+            None,
+            String::from(cwd.join("tests").join("pal").to_str().unwrap()),
+        );
+        let test_config = "tests/pal/check/success.test";
         let job_store_path = cwd.join("tests/pal/check/tests_info/success.json");
         let pal_result = run_pal(
             PalType::Check,
             compile_config,
-            &test_config,
+            &TestConfig::file(test_config),
             job_store_path.to_str().unwrap().to_string(),
-            10,
+            10000,
+            0,
+            GlobLimit::default(),
         );
 
         assert!(pal_result.is_ok());
     }
     #[test]
-    fn test_run_pal_check_ce() {
+    fn test_run_single_file_io() {
+        let cwd = env::current_dir().unwrap();
+        let run = |output: &str| {
+            let compile_config = CompileConfig {
+                file_io: Some(FileIo {
+                    input: String::from("add.in"),
+                    output: String::from(output),
+                }),
+                ..gcc_config(
+                    String::from(cwd.join("tests/pal/check/file_io.c").to_str().unwrap()),
+                    None,
+                    String::from(cwd.join("tests").join("pal").to_str().unwrap()),
+                )
+            };
+            let job = Job {
+                id: 0,
+                input: b"1\n".to_vec(),
+                expected_output: b"2\n".to_vec(),
+                ..Default::default()
+            };
+            let job_store_path = cwd.join("tests/pal/check/tests_info/file_io.json");
+            run_single(
+                PalType::Check,
+                compile_config,
+                job,
+                job_store_path.to_str().unwrap(),
+                10000,
+            )
+            .unwrap()
+        };
+
+        assert!(matches!(run("add.out"), JobResult::Accepted));
+        assert!(matches!(run("missing.out"), JobResult::OtherError(_)));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_kill_process_tree() {
+        let cwd = env::current_dir().unwrap();
+        let compile_config = gcc_config(
+            String::from(cwd.join("tests/pal/check/fork.c").to_str().unwrap()),
+            None,
+            String::from(cwd.join("tests").join("pal").to_str().unwrap()),
+        );
+        let job = Job {
+            id: 0,
+            ..Default::default()
+        };
+        let job_store_path = cwd.join("tests/pal/check/tests_info/fork.json");
+        let job_result = run_single(
+            PalType::Check,
+            compile_config,
+            job,
+            job_store_path.to_str().unwrap(),
+            300,
+        )
+        .unwrap();
+        assert!(matches!(job_result, JobResult::TimeLimitExceed));
+
+        // Forked process is killed with the timed out program soon, it may be left as a zombie
+        let pid = fs::read_to_string("tests/pal/check/tests_info/fork/0/work/pid").unwrap();
+        let killed = || {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+            let state = stat.rsplit_once(')').map(|(_, fields)| fields.trim_start());
+            state.map_or(true, |state| state.starts_with('Z'))
+        };
+        assert!((0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            killed()
+        }));
+    }
+
+    #[test]
+    fn test_run_stress() {
         let cwd = env::current_dir().unwrap();
+        let compile_config = |source: &str| {
+            gcc_config(
+                String::from(cwd.join("tests/pal/stress").join(source).to_str().unwrap()),
+                Some(String::from(
+                    cwd.join("tests/pal/stress/std.c").to_str().unwrap(),
+                )),
+                String::from(cwd.join("tests/pal/stress").to_str().unwrap()),
+            )
+        };
+        let job_store_path = cwd.join("tests/pal/stress/tests_info/stress.json");
+        let job_store_path = job_store_path.to_str().unwrap();
+        let stress_with = |source: &str, test_config: &str, stress_config| {
+            run_stress(
+                compile_config(source),
+                &TestConfig::file(test_config),
+                job_store_path.to_string(),
+                10000,
+                0,
+                stress_config,
+            )
+            .unwrap();
+            parse_store(&fs::read_to_string(job_store_path).unwrap()).unwrap()
+        };
+        let stress = |source: &str, test_config: &str, max_tests, max_failures| {
+            let stress_config = StressConfig {
+                batch_size: 10,
+                max_tests,
+                max_time: None,
+                max_failures,
+            };
+            stress_with(source, test_config, stress_config)
+        };
+
+        // Stop at first failure, which is saved
+        let store = stress("wa.c", "tests/pal/stress/stress.test", None, 1);
+        assert!(store.job_passed.is_empty());
+        assert_eq!(store.job_failed.len(), 1);
+        let (job, job_result, _) = &store.job_failed[0];
+        assert!(matches!(job_result, JobResult::WrongAnswer));
+        let input: u32 = String::from_utf8_lossy(&job.input).trim().parse().unwrap();
+        assert!(input > 90);
+        // Failed test is reproduced by `rpal random-pal --seed 0 --only <id>`
+        let test_config = TestConfig::file("tests/pal/stress/stress.test");
+        let blocks = test_config
+            .random_pal_inputs(&test_config.read().unwrap())
+            .unwrap();
+        let replayed = random_pal_job(&blocks, 0, job.id).unwrap();
+        assert_eq!(
+            (replayed.input, replayed.seed),
+            (job.input.clone(), job.seed)
+        );
+
+        // Time limit is checked after every job, so the rest of the batch is skipped
+        let stress_config = StressConfig {
+            batch_size: 1000,
+            max_tests: None,
+            max_time: Some(Duration::ZERO),
+            max_failures: 1000,
+        };
+        let store = stress_with("wa.c", "tests/pal/stress/fail.test", stress_config);
+        assert!(!store.job_failed.is_empty() && store.job_failed.len() < 1000);
+
+        // Every test fails, only as many as `max_tests` are run
+        let store = stress("wa.c", "tests/pal/stress/fail.test", Some(25), 100);
+        let ids: Vec<usize> = store.job_failed.iter().map(|(job, _, _)| job.id).collect();
+        assert_eq!(ids, (0..25).collect::<Vec<usize>>());
+
+        // Results of retest are reported in order of job list
+        let job_list = store
+            .job_failed
+            .into_iter()
+            .rev()
+            .map(|(job, _, _)| job)
+            .collect();
+        run_retest(
+            PalType::Retest,
+            compile_config("wa.c"),
+            job_list,
+            RetestBase::default(),
+            job_store_path,
+            10000,
+        )
+        .unwrap();
+        let store = parse_store(&fs::read_to_string(job_store_path).unwrap()).unwrap();
+        let ids: Vec<usize> = store.job_failed.iter().map(|(job, _, _)| job.id).collect();
+        assert_eq!(ids, (0..25).rev().collect::<Vec<usize>>());
+
+        // Nothing fails, so nothing is saved, retesting it runs no job
+        let store = stress("std.c", "tests/pal/stress/stress.test", Some(5), 1);
+        assert!(store.job_failed.is_empty());
+        let job_list = store
+            .job_failed
+            .into_iter()
+            .map(|(job, _, _)| job)
+            .collect();
+        run_retest(
+            PalType::Retest,
+            compile_config("std.c"),
+            job_list,
+            RetestBase::default(),
+            job_store_path,
+            10000,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_job_live_std_hash() {
+        let cwd = env::current_dir().unwrap();
+        let std_source = String::from(cwd.join("tests/pal/regenerate/add.c").to_str().unwrap());
+        let compile_config = gcc_config(
+            std_source.clone(),
+            Some(std_source),
+            String::from(cwd.join("tests/pal/regenerate").to_str().unwrap()),
+        );
+        let job_store_path = cwd.join("tests/pal/regenerate/tests_info/live.json");
+        let pal_info = compile(compile_config, 10000, job_store_path.to_str().unwrap()).unwrap();
+        assert!(pal_info.std_hash.is_some());
+        // Expected output saved from an older std program
+        let job = Job {
+            id: 0,
+            input: b"5\n".to_vec(),
+            expected_output: b"0".to_vec(),
+            std_hash: Some(String::from("stale")),
+            ..Default::default()
+        };
+
+        let (retested, job_result) = run_job_live(&PalType::Retest, &pal_info, job.clone());
+        assert!(matches!(job_result, JobResult::WrongAnswer));
+        assert_eq!(retested.std_hash.as_deref(), Some("stale"));
+
+        let (regenerated, job_result) = run_job_live(&PalType::RandomPal, &pal_info, job);
+        assert!(matches!(job_result, JobResult::Accepted));
+        assert_eq!(regenerated.expected_output, b"6");
+        assert_eq!(regenerated.std_hash, pal_info.std_hash);
+    }
+
+    #[test]
+    fn test_generator_checker_in_job_dir() {
+        let cwd = env::current_dir().unwrap();
+        let dir = cwd.join("tests/pal/jobdir");
+        let path = |name: &str| String::from(dir.join(name).to_str().unwrap());
         let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args: String::from("-Wall -Wextra -lm"),
-            source: String::from(
+            generator_source: Some(path("gen.c")),
+            checker_source: Some(path("checker.c")),
+            // Generator and checker read base.txt, which is only in working directory of job
+            fixtures: vec![path("fixture/base.txt")],
+            ..gcc_config(path("echo.c"), None, path(""))
+        };
+        let job_store_path = dir.join("tests_info/jobdir.json");
+        let mut pal_info =
+            compile(compile_config, 10000, job_store_path.to_str().unwrap()).unwrap();
+        let job = Job {
+            id: 0,
+            seed: Some(3),
+            ..Default::default()
+        };
+
+        let (job, job_result) = run_job(Arc::new(PalType::Check), Arc::new(pal_info.clone()), job);
+        assert!(matches!(job_result, JobResult::Accepted));
+        assert_eq!(job.input, b"10\n");
+
+        pal_info.fixtures.clear();
+        let (_, job_result) = run_job(Arc::new(PalType::Check), Arc::new(pal_info), job);
+        assert!(matches!(job_result, JobResult::GeneratorError(_)));
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        )),
+        ignore
+    )]
+    fn test_sandbox() {
+        let cwd = env::current_dir().unwrap();
+        let dir = cwd.join("tests/pal/sandbox");
+        let run = |source: &str, expected_output: &str| {
+            let compile_config = CompileConfig {
+                sandbox: true,
+                ..gcc_config(
+                    String::from(dir.join(source).to_str().unwrap()),
+                    None,
+                    String::from(dir.to_str().unwrap()),
+                )
+            };
+            let job_store_path = dir.join("tests_info/sandbox.json");
+            let pal_info =
+                compile(compile_config, 10000, job_store_path.to_str().unwrap()).unwrap();
+            let job = Job {
+                expected_output: expected_output.as_bytes().to_vec(),
+                ..Default::default()
+            };
+            run_job(Arc::new(PalType::Check), Arc::new(pal_info), job).1
+        };
+
+        assert!(matches!(run("inet.c", ""), JobResult::SecurityViolation));
+        assert!(matches!(run("unix.c", "1"), JobResult::Accepted));
+        assert!(matches!(run("write.c", "EROFS 1"), JobResult::Accepted));
+    }
+
+    #[test]
+    fn test_run_pal_check_ce() {
+        let cwd = env::current_dir().unwrap();
+        let compile_config = gcc_config(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("check")
@@ -594,23 +1624,25 @@ mod tests {
                     .to_str()
                     .unwrap(),
             ),
-            std_source: None,
-            work_directory: String::from(
+            None,
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("check")
                     .to_str()
                     .unwrap(),
             ),
-        };
-        let test_config = fs::read_to_string("tests/pal/check/ce.test").unwrap();
+        );
+        let test_config = "tests/pal/check/ce.test";
         let job_store_path = cwd.join("tests/pal/check/tests_info/ce.json");
         let pal_result = run_pal(
             PalType::Check,
             compile_config,
-            &test_config,
+            &TestConfig::file(test_config),
             job_store_path.to_str().unwrap().to_string(),
-            10,
+            10000,
+            0,
+            GlobLimit::default(),
         );
 
         match pal_result {
@@ -621,10 +1653,8 @@ mod tests {
     #[test]
     fn test_run_pal_check_wa() {
         let cwd = env::current_dir().unwrap();
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args: String::from("-Wall -Wextra -lm"),
-            source: String::from(
+        let compile_config = gcc_config(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("check")
@@ -632,23 +1662,25 @@ mod tests {
                     .to_str()
                     .unwrap(),
             ),
-            std_source: None,
-            work_directory: String::from(
+            None,
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("check")
                     .to_str()
                     .unwrap(),
             ),
-        };
-        let test_config = fs::read_to_string("tests/pal/check/wa.test").unwrap();
+        );
+        let test_config = "tests/pal/check/wa.test";
         let job_store_path = cwd.join("tests/pal/check/tests_info/ce.json");
         let pal_result: Result<(), PalError> = run_pal(
             PalType::Check,
             compile_config,
-            &test_config,
+            &TestConfig::file(test_config),
             job_store_path.to_str().unwrap().to_string(),
-            10,
+            10000,
+            0,
+            GlobLimit::default(),
         );
         assert!(pal_result.is_ok());
     }
@@ -657,10 +1689,8 @@ mod tests {
     #[ignore]
     fn test_run_pal_pal_success() {
         let cwd = env::current_dir().unwrap();
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args: String::from("-Wall -Wextra -lm"),
-            source: String::from(
+        let compile_config = gcc_config(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("pal")
@@ -668,7 +1698,7 @@ mod tests {
                     .to_str()
                     .unwrap(),
             ),
-            std_source: Some(String::from(
+            Some(String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("pal")
@@ -676,18 +1706,18 @@ mod tests {
                     .to_str()
                     .unwrap(),
             )),
-            work_directory: String::from(
-                cwd.join("tests").join("pal").join("pal").to_str().unwrap(),
-            ),
-        };
-        let test_config = fs::read_to_string("tests/pal/pal/success.test").unwrap();
+            String::from(cwd.join("tests").join("pal").join("pal").to_str().unwrap()),
+        );
+        let test_config = "tests/pal/pal/success.test";
         let job_store_path = cwd.join("tests/pal/pal/tests_info/success.json");
         let pal_result = run_pal(
             PalType::Pal,
             compile_config,
-            &test_config,
+            &TestConfig::file(test_config),
             job_store_path.to_str().unwrap().to_string(),
-            10,
+            10000,
+            0,
+            GlobLimit::default(),
         );
 
         assert!(pal_result.is_ok());
@@ -697,10 +1727,8 @@ mod tests {
     #[ignore]
     fn test_run_pal_random_success() {
         let cwd = env::current_dir().unwrap();
-        let compile_config = CompileConfig {
-            compiler: String::from("gcc"),
-            args: String::from("-Wall -Wextra -lm"),
-            source: String::from(
+        let compile_config = gcc_config(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("random_pal")
@@ -708,7 +1736,7 @@ mod tests {
                     .to_str()
                     .unwrap(),
             ),
-            std_source: Some(String::from(
+            Some(String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("random_pal")
@@ -716,22 +1744,24 @@ mod tests {
                     .to_str()
                     .unwrap(),
             )),
-            work_directory: String::from(
+            String::from(
                 cwd.join("tests")
                     .join("pal")
                     .join("random_pal")
                     .to_str()
                     .unwrap(),
             ),
-        };
-        let test_config = fs::read_to_string("tests/pal/random_pal/success.test").unwrap();
+        );
+        let test_config = "tests/pal/random_pal/success.test";
         let job_store_path = cwd.join("tests/pal/random_pal/tests_info/success.json");
         let pal_result = run_pal(
             PalType::RandomPal,
             compile_config,
-            &test_config,
+            &TestConfig::file(test_config),
             job_store_path.to_str().unwrap().to_string(),
-            10,
+            10000,
+            0,
+            GlobLimit::default(),
         );
 
         assert!(pal_result.is_ok());
@@ -1,12 +1,37 @@
+use crate::datadir::DataLayout;
+use crate::directive::{
+    check_directives, directive_spans, enumerate_variables, expand_directives, Variables, Variants,
+};
 use crate::job::Job;
+use crate::limits::Limits;
 use crate::pal::PalType;
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use crate::problem::{Problem, ProblemFormat, Subtask};
+use num_bigint::{BigInt, BigUint, RandBigInt};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
 
 pub enum ParseError {
     UnexpectedEOF(String),
     FormatError(String),
     UnkownInputType(String),
+    /// Error at a position of test config, line and column start from 1
+    Located {
+        file: Option<String>,
+        line: usize,
+        column: usize,
+        error: Box<ParseError>,
+    },
+    /// Error in a case of structured test config
+    InCase {
+        case: String,
+        error: Box<ParseError>,
+    },
 }
 
 impl std::fmt::Debug for ParseError {
@@ -15,60 +40,574 @@ impl std::fmt::Debug for ParseError {
             Self::UnexpectedEOF(e) => write!(f, "Unexpected EOF: {}", e),
             Self::FormatError(e) => write!(f, "Format error: {}", e),
             Self::UnkownInputType(e) => write!(f, "Unkown config type: {}", e),
+            Self::Located {
+                file,
+                line,
+                column,
+                error,
+            } => match file {
+                Some(file) => write!(f, "{}:{}:{}: {:?}", file, line, column, error),
+                None => write!(f, "line {}, column {}: {:?}", line, column, error),
+            },
+            Self::InCase { case, error } => write!(f, "{}: {:?}", case, error),
         }
     }
 }
 
-pub fn parse(pal_type: &PalType, input: &str) -> Result<Vec<Job>, ParseError> {
+impl ParseError {
+    pub(crate) fn at(self, line: usize, column: usize) -> ParseError {
+        match self {
+            Self::Located { .. } => self,
+            error => Self::Located {
+                file: None,
+                line,
+                column,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Attach path of test config to a located error
+    pub fn in_file(self, path: &str) -> ParseError {
+        match self {
+            Self::Located {
+                line,
+                column,
+                error,
+                ..
+            } => Self::Located {
+                file: Some(path.to_string()),
+                line,
+                column,
+                error,
+            },
+            error => error,
+        }
+    }
+}
+
+/// Result with byte offset of error in input
+pub(crate) type OffsetResult<T> = Result<T, (usize, String)>;
+
+/// An unexpanded input in test config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Line number of the first line of input
+    pub line: usize,
+    pub input: String,
+    /// Text after the separator ending this input, e.g. `x500` in `--- x500`
+    pub annotation: String,
+}
+
+impl Block {
+    /// Line and column of byte `offset` in input
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.input[..offset];
+        let line = self.line + before.matches('\n').count();
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column)
+    }
+
+    /// Line number of the separator ending input
+    fn end_line(&self) -> usize {
+        self.line + self.input.matches('\n').count()
+    }
+
+    /// Check directives and patterns, so syntax errors are reported at where they are
+    pub fn check(&self) -> Result<(), ParseError> {
+        check_directives(&self.input, &mut Variables::new()).map_err(|(offset, e)| {
+            let (line, column) = self.position(offset);
+            ParseError::FormatError(e).at(line, column)
+        })
+    }
+}
+
+/// Where tests are read from, a test config file or a directory of test data
+#[derive(Clone, Debug)]
+pub struct TestConfig {
+    pub path: String,
+    /// How input and answer files are named, if `path` is a test data directory
+    pub data_layout: Option<DataLayout>,
+}
+
+impl TestConfig {
+    pub fn file(path: &str) -> TestConfig {
+        TestConfig {
+            path: path.to_string(),
+            data_layout: None,
+        }
+    }
+
+    /// Content of test config, a test data directory is read as Check config including its files
+    pub fn read(&self) -> Result<String, String> {
+        match self.data_layout {
+            Some(layout) => layout.check_config(Path::new(&self.path)),
+            None => fs::read_to_string(&self.path)
+                .map_err(|e| format!("Cannot read test config: {:?}", e)),
+        }
+    }
+
+    /// Attach path of test config to a located error. Positions in config generated for test
+    /// data directory mean nothing to user, so they are dropped. Errors of structured test
+    /// config are located when it's read.
+    pub fn locate(&self, error: ParseError) -> ParseError {
+        match (self.data_layout, error) {
+            (Some(_), ParseError::Located { error, .. }) => *error,
+            (None, error) if self.problem_format().is_none() => error.in_file(&self.path),
+            (_, error) => error,
+        }
+    }
+
+    /// Directory which files included by test config are relative to
+    pub fn base(&self) -> &Path {
+        match self.data_layout {
+            Some(_) => Path::new(&self.path),
+            None => Path::new(&self.path).parent().unwrap_or(Path::new("")),
+        }
+    }
+
+    /// Format of test config if it's a structured one, detected from its extension
+    pub fn problem_format(&self) -> Option<ProblemFormat> {
+        match self.data_layout {
+            Some(_) => None,
+            None => ProblemFormat::of(Path::new(&self.path)),
+        }
+    }
+
+    /// Structured test config read from `input`, None if test config is of separators
+    pub fn problem(&self, input: &str) -> Result<Option<Problem>, ParseError> {
+        self.problem_format()
+            .map(|format| Problem::parse(input, format).map_err(|e| e.in_file(&self.path)))
+            .transpose()
+    }
+
+    /// Same as `parse_lazy`, for test config of any format
+    pub fn parse_lazy<'a>(
+        &self,
+        pal_type: &PalType,
+        input: &'a str,
+        seed: u64,
+        limit: GlobLimit,
+    ) -> Result<(usize, JobIter<'a>), ParseError> {
+        match self.problem(input)? {
+            Some(problem) => jobs_of_blocks(problem.blocks(pal_type, self.base())?, seed, limit),
+            None => parse_lazy(pal_type, input, self.base(), seed, limit),
+        }
+    }
+
+    /// Same as `count_blocks`, with inputs labeled by where they are, e.g. `line 3`
+    pub fn count_blocks(
+        &self,
+        pal_type: &PalType,
+        input: &str,
+        cap: u128,
+    ) -> Result<Vec<(String, u128)>, ParseError> {
+        match self.problem(input)? {
+            Some(problem) => Ok(count_jobs(problem.blocks(pal_type, self.base())?, cap)?
+                .into_iter()
+                .map(|(position, count)| (problem.label(position), count))
+                .collect()),
+            None => Ok(count_blocks(pal_type, input, self.base(), cap)?
+                .into_iter()
+                .map(|(line, count)| (format!("line {}", line), count))
+                .collect()),
+        }
+    }
+
+    /// Subtasks worth points, only defined in structured configs
+    pub fn subtasks(&self, input: &str) -> Result<Vec<Subtask>, ParseError> {
+        Ok(self
+            .problem(input)?
+            .map(|problem| problem.subtasks())
+            .unwrap_or_default())
+    }
+
+    /// Same as `parse_random_pal_inputs`, for test config of any format
+    pub fn random_pal_inputs(&self, input: &str) -> Result<Vec<RandomInput>, ParseError> {
+        match self.problem(input)? {
+            Some(problem) => problem.random_inputs().map(|inputs| {
+                inputs
+                    .into_iter()
+                    .map(|(_, label, block, count)| (block, label, count))
+                    .collect()
+            }),
+            None => parse_random_pal_inputs(input),
+        }
+    }
+}
+
+/// Jobs generated lazily from test config
+pub type JobIter<'a> = Box<dyn Iterator<Item = Result<Job, ParseError>> + 'a>;
+
+/// Limit of number of tests expanded from glob patterns
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct GlobLimit {
+    pub max_combinations: u128,
+    /// Run randomly chosen tests instead of refusing, if there are more combinations
+    pub sample: bool,
+}
+
+impl Default for GlobLimit {
+    fn default() -> Self {
+        GlobLimit {
+            max_combinations: 1_000_000,
+            sample: false,
+        }
+    }
+}
+
+impl GlobLimit {
+    pub const UNLIMITED: GlobLimit = GlobLimit {
+        max_combinations: u128::MAX,
+        sample: false,
+    };
+}
+
+/// Jobs of an input in test config
+pub(crate) enum BlockJobs {
+    /// Input(and expected output) written in test config, or read from files it includes
+    Fixed(Vec<Job>),
+    Glob(Block),
+    Random(Block, usize),
+    Generated(usize),
+}
+
+impl BlockJobs {
+    /// Number of jobs, counting of glob combinations may stop once it's more than `cap`
+    fn count(&self, cap: u128) -> Result<u128, ParseError> {
+        match self {
+            BlockJobs::Fixed(jobs) => Ok(jobs.len() as u128),
+            BlockJobs::Glob(block) => count_glob(block, cap),
+            BlockJobs::Random(_, count) | BlockJobs::Generated(count) => Ok(*count as u128),
+        }
+    }
+}
+
+/// An input in test config and its jobs
+pub(crate) struct ConfigBlock {
+    /// Line of input in test config, or number of case in structured test config
+    pub position: usize,
+    pub label: Label,
+    pub jobs: BlockJobs,
+}
+
+impl ConfigBlock {
+    pub(crate) fn new(position: usize, jobs: BlockJobs) -> ConfigBlock {
+        ConfigBlock {
+            position,
+            label: Label::default(),
+            jobs,
+        }
+    }
+
+    pub(crate) fn with_label(self, label: Label) -> ConfigBlock {
+        ConfigBlock { label, ..self }
+    }
+}
+
+/// Group and name of an input, written as `[group]` or `[group: name]` at the end of
+/// annotation. Inputs of structured test config can also have limits.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub group: Option<String>,
+    /// Name of jobs, followed by names they already have
+    pub name: Option<String>,
+    pub limits: Limits,
+}
+
+impl Label {
+    /// Job of input with this label
+    fn apply(&self, mut job: Job) -> Job {
+        job.name = join_name(&self.name, job.name);
+        job.group = self.group.clone();
+        job.time_limit = self.limits.time;
+        job.memory_limit = self.limits.memory;
+        job
+    }
+
+    /// Split label from the end of annotation, returned with the rest of annotation
+    pub(crate) fn parse(annotation: &str) -> Result<(&str, Label), String> {
+        let (rest, label) = match annotation
+            .strip_suffix(']')
+            .and_then(|a| a.rsplit_once('['))
+        {
+            Some((rest, label)) => (rest.trim_end(), label),
+            None => return Ok((annotation, Label::default())),
+        };
+        let (group, name) = match label.split_once(':') {
+            Some((group, name)) => (group.trim(), Some(name.trim())),
+            None => (label.trim(), None),
+        };
+        if group.is_empty() {
+            return Err(format!("Group is missing in label: [{}]", label));
+        }
+        Ok((
+            rest,
+            Label {
+                group: Some(group.to_string()),
+                name: name.filter(|name| !name.is_empty()).map(str::to_string),
+                limits: Limits::default(),
+            },
+        ))
+    }
+}
+
+/// Name of job in input named `name`, if the job is named `job_name` on its own
+fn join_name(name: &Option<String>, job_name: Option<String>) -> Option<String> {
+    match (name, job_name) {
+        (Some(name), Some(job_name)) => Some(format!("{}/{}", name, job_name)),
+        (name, job_name) => job_name.or_else(|| name.clone()),
+    }
+}
+
+/// Parse test config into jobs. Random inputs(and seeds passed to generator) are derived from
+/// `seed`, so the same config and seed always produce the same jobs.
+/// Files included by `@file:` and `@glob:` are relative to current directory.
+pub fn parse(pal_type: &PalType, input: &str, seed: u64) -> Result<Vec<Job>, ParseError> {
+    let (_, jobs) = parse_lazy(pal_type, input, Path::new(""), seed, GlobLimit::UNLIMITED)?;
+    jobs.collect()
+}
+
+/// Same as `parse`, but jobs are generated when iterated, returned with number of jobs.
+/// Included files are relative to `base`, the directory of test config. If glob patterns
+/// expand to more than `limit.max_combinations` jobs, it's refused, or
+/// `limit.max_combinations` randomly expanded jobs are generated if `limit.sample` is set.
+pub fn parse_lazy<'a>(
+    pal_type: &PalType,
+    input: &'a str,
+    base: &Path,
+    seed: u64,
+    limit: GlobLimit,
+) -> Result<(usize, JobIter<'a>), ParseError> {
+    jobs_of_blocks(parse_blocks(pal_type, input, base)?, seed, limit)
+}
+
+/// Generate jobs of inputs lazily, returned with number of jobs
+pub(crate) fn jobs_of_blocks<'a>(
+    blocks: Vec<ConfigBlock>,
+    seed: u64,
+    limit: GlobLimit,
+) -> Result<(usize, JobIter<'a>), ParseError> {
+    let counts = blocks
+        .iter()
+        .map(|block| block.jobs.count(limit.max_combinations))
+        .collect::<Result<Vec<u128>, ParseError>>()?;
+    let combinations = blocks
+        .iter()
+        .zip(&counts)
+        .filter(|(block, _)| matches!(block.jobs, BlockJobs::Glob(_)))
+        .fold(0u128, |total, (_, count)| total.saturating_add(*count));
+
+    if combinations > limit.max_combinations {
+        if !limit.sample {
+            return Err(ParseError::FormatError(format!(
+                "Glob patterns expand to more than {} tests. Use --sample to run randomly \
+                chosen tests, or raise the limit with --max-combinations.",
+                limit.max_combinations
+            )));
+        }
+        println!(
+            "Glob patterns expand to more than {} tests, running randomly chosen ones",
+            limit.max_combinations
+        );
+        return Ok((
+            limit.max_combinations.min(usize::MAX as u128) as usize,
+            sample_glob(blocks, counts, combinations, seed, limit.max_combinations),
+        ));
+    }
+
+    let total = counts
+        .iter()
+        .fold(0u128, |total, count| total.saturating_add(*count))
+        .min(usize::MAX as u128) as usize;
+    let mut first_id: usize = 0;
+    let mut iters = Vec::new();
+    for (index, (block, count)) in blocks.into_iter().zip(counts).enumerate() {
+        let start = first_id;
+        first_id = first_id.saturating_add(count.min(usize::MAX as u128) as usize);
+        let ConfigBlock { label, jobs, .. } = block;
+        let iter: JobIter =
+            match jobs {
+                BlockJobs::Fixed(jobs) => {
+                    Box::new(jobs.into_iter().zip(start..).map(move |(mut job, id)| {
+                        job.id = id;
+                        Ok(job)
+                    }))
+                }
+                BlockJobs::Glob(glob) => {
+                    let rng = ChaCha8Rng::seed_from_u64(job_seed(seed, index));
+                    Box::new(GlobExpansion::new(glob.input, rng).enumerate().map(
+                        move |(k, input)| {
+                            expanded_job(start + k, None, &input.map_err(|e| e.at(glob.line, 1))?)
+                                .map_err(|e| ParseError::FormatError(e).at(glob.line, 1))
+                        },
+                    ))
+                }
+                BlockJobs::Random(random, count) => {
+                    Box::new((start..start + count).map(move |id| random_job(&random, seed, id)))
+                }
+                BlockJobs::Generated(count) => Box::new((start..start + count).map(move |id| {
+                    Ok(Job {
+                        id,
+                        // Generator usually reads seed with atoi(), keep it in range of int
+                        seed: Some(job_seed(seed, id) & 0x7fffffff),
+                        ..Default::default()
+                    })
+                })),
+            };
+        iters.push(Box::new(
+            iter.map(move |job| job.map(|job| label.apply(job))),
+        ));
+    }
+
+    Ok((total, Box::new(iters.into_iter().flatten())))
+}
+
+/// Number of jobs from each input of test config, with line of the input. Glob combinations
+/// are counted until there are more than `cap`.
+pub fn count_blocks(
+    pal_type: &PalType,
+    input: &str,
+    base: &Path,
+    cap: u128,
+) -> Result<Vec<(usize, u128)>, ParseError> {
+    count_jobs(parse_blocks(pal_type, input, base)?, cap)
+}
+
+pub(crate) fn count_jobs(
+    blocks: Vec<ConfigBlock>,
+    cap: u128,
+) -> Result<Vec<(usize, u128)>, ParseError> {
+    blocks
+        .into_iter()
+        .map(|block| Ok((block.position, block.jobs.count(cap)?)))
+        .collect()
+}
+
+/// Count which may have stopped after exceeding `cap`
+pub fn format_count(count: u128, cap: u128) -> String {
+    if count > cap {
+        format!("more than {}", cap)
+    } else {
+        count.to_string()
+    }
+}
+
+/// Randomly expand glob inputs, chosen with probability proportional to their combinations
+fn sample_glob<'a>(
+    blocks: Vec<ConfigBlock>,
+    counts: Vec<u128>,
+    combinations: u128,
+    seed: u64,
+    sample_count: u128,
+) -> JobIter<'a> {
+    let blocks: Vec<(Block, Label, u128)> = blocks
+        .into_iter()
+        .zip(counts)
+        .filter_map(|(block, count)| match block.jobs {
+            BlockJobs::Glob(glob) => Some((glob, block.label, count)),
+            _ => None,
+        })
+        .collect();
+    Box::new((0..sample_count as usize).map(move |id| {
+        let job_seed = job_seed(seed, id);
+        let mut rng = ChaCha8Rng::seed_from_u64(job_seed);
+        let mut chosen = rng.gen_range(0..combinations);
+        let (block, label, _) = blocks
+            .iter()
+            .find(|(_, _, count)| {
+                let found = chosen < *count;
+                chosen = chosen.saturating_sub(*count);
+                found
+            })
+            .unwrap_or_else(|| blocks.last().unwrap());
+        let input =
+            expand_random(block.input.clone(), &mut rng).map_err(|e| e.at(block.line, 1))?;
+        expanded_job(id, Some(job_seed), &input)
+            .map(|job| label.apply(job))
+            .map_err(|e| ParseError::FormatError(e).at(block.line, 1))
+    }))
+}
+
+/// Split test config into inputs
+fn parse_blocks(
+    pal_type: &PalType,
+    input: &str,
+    base: &Path,
+) -> Result<Vec<ConfigBlock>, ParseError> {
     match &pal_type {
-        PalType::Check => parse_check(input),
-        PalType::Pal => parse_pal(input),
+        PalType::Check => parse_check(input, base),
+        PalType::Pal => parse_pal(input, base),
         PalType::RandomPal => parse_random_pal(input),
         _ => unreachable!(),
     }
 }
 
-fn parse_check(input: &str) -> Result<Vec<Job>, ParseError> {
+fn parse_check(input: &str, base: &Path) -> Result<Vec<ConfigBlock>, ParseError> {
     let mut result = Vec::new();
     let mut input_lines = input.lines();
     let separator = input_lines.next().ok_or_else(|| {
-        ParseError::UnexpectedEOF(String::from("Input ends when reading separator."))
+        ParseError::UnexpectedEOF(String::from("Input ends when reading separator.")).at(1, 1)
     })?;
 
     let mut is_output = false;
 
     let mut this_input = Vec::new();
     let mut this_output = Vec::new();
+    let mut this_line = 2;
 
-    let mut this_id = 0;
+    let mut this_label = Label::default();
 
-    for line in input_lines {
-        if line != separator {
-            // input
-            if !is_output {
-                let mut line = String::from(line);
-                line.push('\n');
-                this_input.push(line);
-            } else {
-                let mut line = String::from(line);
-                line.push('\n');
-                this_output.push(line);
+    for (line_number, line) in (2..).zip(input_lines) {
+        match separator_annotation(line, separator) {
+            None => {
+                // input
+                if !is_output {
+                    let mut line = String::from(line);
+                    line.push('\n');
+                    this_input.push(line);
+                } else {
+                    let mut line = String::from(line);
+                    line.push('\n');
+                    this_output.push(line);
+                }
             }
-        } else {
-            if !is_output {
+            // label of case is written after separator ending input
+            Some(annotation) if !is_output => {
                 is_output = true;
-            } else {
+                let column = separator.len() + 2;
+                this_label = match Label::parse(annotation) {
+                    Ok(("", label)) => label,
+                    Ok((rest, _)) => {
+                        return Err(ParseError::FormatError(format!(
+                            "Unexpected annotation after separator: {}",
+                            rest
+                        ))
+                        .at(line_number, column))
+                    }
+                    Err(e) => return Err(ParseError::FormatError(e).at(line_number, column)),
+                };
+            }
+            Some("") => {
                 is_output = false;
 
-                result.push(Job {
-                    id: this_id,
-                    input: this_input.concat().as_bytes().to_vec(),
-                    expected_output: this_output.concat().as_bytes().to_vec(),
-                    actual_output: Vec::new(),
-                });
-                this_id += 1;
+                let jobs = check_jobs(&this_input.concat(), &this_output.concat(), base)
+                    .map_err(|e| ParseError::FormatError(e).at(this_line, 1))?;
+                result.push(
+                    ConfigBlock::new(this_line, BlockJobs::Fixed(jobs))
+                        .with_label(std::mem::take(&mut this_label)),
+                );
                 this_input.clear();
                 this_output.clear();
+                this_line = line_number + 1;
+            }
+            Some(annotation) => {
+                return Err(ParseError::FormatError(format!(
+                    "Unexpected annotation after expected output: {}, label is written after \
+                    separator ending input",
+                    annotation
+                ))
+                .at(line_number, separator.len() + 2))
             }
         }
     }
@@ -76,239 +615,962 @@ fn parse_check(input: &str) -> Result<Vec<Job>, ParseError> {
     Ok(result)
 }
 
-fn parse_pal(input: &str) -> Result<Vec<Job>, ParseError> {
+fn parse_pal(input: &str, base: &Path) -> Result<Vec<ConfigBlock>, ParseError> {
     let mut result = Vec::new();
     let mut input_lines = input.lines();
     let config_type = input_lines.next().ok_or_else(|| {
-        ParseError::UnexpectedEOF(String::from("Input ends when reading config type."))
+        ParseError::UnexpectedEOF(String::from("Input ends when reading config type.")).at(1, 1)
     })?;
 
     match config_type {
         "simple" => {
             let separator = input_lines.next().ok_or_else(|| {
                 ParseError::UnexpectedEOF(String::from("Input ends when reading separator."))
+                    .at(2, 1)
             })?;
 
-            let mut this_input = Vec::new();
-
-            let mut this_id = 0;
-
-            for line in input_lines {
-                if line != separator {
-                    let mut line = String::from(line);
-                    line.push('\n');
-                    this_input.push(line);
-                } else {
-                    result.push(Job {
-                        id: this_id,
-                        input: this_input.concat().as_bytes().to_vec(),
-                        expected_output: Vec::new(),
-                        actual_output: Vec::new(),
-                    });
-                    this_input.clear();
-                    this_id += 1;
-                }
+            for block in split_blocks(input_lines, separator, 3) {
+                let label = only_label(&block, separator)?;
+                let jobs = pal_jobs(&block.input, base)
+                    .map_err(|e| ParseError::FormatError(e).at(block.line, 1))?;
+                result.push(ConfigBlock::new(block.line, BlockJobs::Fixed(jobs)).with_label(label));
             }
         }
         "glob" => {
-            let mut raw_inputs = Vec::new();
             let separator = input_lines.next().ok_or_else(|| {
                 ParseError::UnexpectedEOF(String::from("Input ends when reading separator."))
+                    .at(2, 1)
             })?;
-            let mut this_input = Vec::new();
 
-            for line in input_lines {
-                if line != separator {
-                    let mut line = String::from(line);
-                    line.push('\n');
-                    this_input.push(line);
-                } else {
-                    raw_inputs.push(this_input.concat());
-                    this_input.clear();
+            for block in split_blocks(input_lines, separator, 3) {
+                let label = only_label(&block, separator)?;
+                block.check()?;
+                result.push(ConfigBlock::new(block.line, BlockJobs::Glob(block)).with_label(label));
+            }
+        }
+        "generator" => {
+            // generator source is resolved by caller, see `parse_generator_source`
+            input_lines.next().ok_or_else(|| {
+                ParseError::UnexpectedEOF(String::from("Input ends when reading generator source."))
+                    .at(2, 1)
+            })?;
+            let tests_num = input_lines.next().ok_or_else(|| {
+                ParseError::UnexpectedEOF(String::from("Input ends when reading tests num."))
+                    .at(3, 1)
+            })?;
+            let tests_num: usize = tests_num.parse().map_err(|e| {
+                ParseError::FormatError(format!("Cannot parse tests num({}): {:?}", tests_num, e))
+                    .at(3, 1)
+            })?;
+
+            result.push(ConfigBlock::new(2, BlockJobs::Generated(tests_num)));
+        }
+        x => return Err(ParseError::UnkownInputType(x.to_string()).at(1, 1)),
+    }
+    Ok(result)
+}
+
+/// Split lines into inputs ended by separator, `first_line` is line number of the first line.
+/// A separator may be followed by a space and an annotation of the input it ends.
+fn split_blocks<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    separator: &str,
+    first_line: usize,
+) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut this_input = Vec::new();
+    let mut this_line = first_line;
+
+    for (line_number, line) in (first_line..).zip(lines) {
+        if let Some(annotation) = separator_annotation(line, separator) {
+            blocks.push(Block {
+                line: this_line,
+                input: this_input.concat(),
+                annotation: annotation.to_string(),
+            });
+            this_input.clear();
+            this_line = line_number + 1;
+        } else {
+            let mut line = String::from(line);
+            line.push('\n');
+            this_input.push(line);
+        }
+    }
+    blocks
+}
+
+/// Annotation if `line` is a separator, which may be followed by a space and an annotation:
+/// number of tests like `x500` or `30%`, and a label like `[group: name]`. Other lines
+/// starting with separator, like `--- note`, are input.
+fn separator_annotation<'a>(line: &'a str, separator: &str) -> Option<&'a str> {
+    static SHARE_RE: OnceLock<Regex> = OnceLock::new();
+    let annotation = match line.strip_prefix(separator)? {
+        "" => return Some(""),
+        rest => rest.strip_prefix(' ')?.trim(),
+    };
+    let share = match Label::parse(annotation) {
+        Ok((share, _)) => share,
+        // Malformed label is reported where annotation is parsed
+        Err(_) => return Some(annotation),
+    };
+    let share_re = SHARE_RE.get_or_init(|| Regex::new(r"^(x\s*\d+|\d+\s*%)?$").unwrap());
+    (!annotation.is_empty() && share_re.is_match(share)).then_some(annotation)
+}
+
+/// Label of input in configs whose annotations can only be labels
+fn only_label(block: &Block, separator: &str) -> Result<Label, ParseError> {
+    let position = |e: String| ParseError::FormatError(e).at(block.end_line(), separator.len() + 2);
+    match Label::parse(&block.annotation).map_err(position)? {
+        ("", label) => Ok(label),
+        (rest, _) => Err(position(format!(
+            "Unexpected annotation after separator: {}",
+            rest
+        ))),
+    }
+}
+
+/// Files referenced by an input or output in Check and Pal configs
+enum Include<'a> {
+    /// `@file:cases/1.in`
+    File(&'a str),
+    /// `@glob:cases/*.in`, `*` and `?` can be used in file name
+    Glob(&'a str),
+}
+
+impl Include<'_> {
+    /// Input or output of a single line `@file:path` or `@glob:pattern`
+    fn parse(text: &str) -> Option<Include<'_>> {
+        let line = text.strip_suffix('\n').unwrap_or(text);
+        if line.contains('\n') {
+            None
+        } else if let Some(path) = line.strip_prefix("@file:") {
+            Some(Include::File(path.trim()))
+        } else {
+            line.strip_prefix("@glob:")
+                .map(|pattern| Include::Glob(pattern.trim()))
+        }
+    }
+}
+
+/// Command-line arguments and environment variables of tested(and std) program, written as
+/// `@args: --mode fast` and `@env: KEY=value` lines at the start of an input
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Invocation {
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+impl Invocation {
+    /// Split leading `@args:` and `@env:` lines from input, returned with the rest of input
+    fn parse(input: &str) -> Result<(Invocation, &str), String> {
+        let mut invocation = Invocation::default();
+        let mut rest = input;
+        loop {
+            let (line, next) = rest.split_once('\n').unwrap_or((rest, ""));
+            if let Some(args) = line.strip_prefix("@args:") {
+                invocation.args.extend(split_args(args)?);
+            } else if let Some(variable) = line.strip_prefix("@env:") {
+                let variable = variable.trim();
+                match variable.split_once('=') {
+                    Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+                        invocation.env.push((key.to_string(), value.to_string()))
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Invalid environment variable: {}, should be like KEY=value",
+                            variable
+                        ))
+                    }
                 }
+            } else {
+                return Ok((invocation, rest));
             }
+            rest = next;
+        }
+    }
 
-            let mut this_id = 0;
+    /// Job run with these arguments and environment variables
+    fn apply(&self, mut job: Job) -> Job {
+        job.args = self.args.clone();
+        job.env = self.env.clone();
+        job
+    }
+}
 
-            for raw_input in raw_inputs {
-                for expanded_input in expand_glob(raw_input) {
-                    result.push(Job {
-                        id: this_id,
-                        input: expanded_input.as_bytes().to_vec(),
-                        expected_output: Vec::new(),
-                        actual_output: Vec::new(),
-                    });
-                    this_id += 1;
+/// Split arguments by whitespace, an argument with spaces can be quoted by `"` or `'`
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => arg.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => args.extend(arg.take()),
+            None => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unclosed quote in arguments: {}", line.trim()));
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Jobs of a case in Check config, an input of `@glob:` is paired with output files of the
+/// same name, given by `@glob:` output(wildcards replaced by what they matched in order) or
+/// `.out` extension
+pub(crate) fn check_jobs(input: &str, output: &str, base: &Path) -> Result<Vec<Job>, String> {
+    let (invocation, input) = Invocation::parse(input)?;
+    let job = |name: Option<String>, input: Vec<u8>, expected_output: Vec<u8>| Job {
+        id: 0,
+        input,
+        expected_output,
+        name,
+        args: invocation.args.clone(),
+        env: invocation.env.clone(),
+        ..Default::default()
+    };
+    let read_output = |output: &str| match Include::parse(output) {
+        Some(Include::File(path)) => read_include(base, path),
+        Some(Include::Glob(_)) => Err(String::from("Output can be @glob: only if input is @glob:")),
+        None => Ok(output.as_bytes().to_vec()),
+    };
+
+    match Include::parse(input) {
+        Some(Include::Glob(pattern)) => {
+            let files = glob_files(base, pattern)?;
+            let output_pattern = match Include::parse(output) {
+                Some(Include::Glob(output_pattern)) => Some(output_pattern),
+                None if output.is_empty() => None,
+                _ => {
+                    return Err(String::from(
+                        "Output of @glob: input should be empty or @glob:",
+                    ))
                 }
+            };
+            files
+                .into_iter()
+                .map(|(path, matched)| {
+                    let output_path = match output_pattern {
+                        Some(output_pattern) => {
+                            let mut matched = matched.iter();
+                            output_pattern
+                                .chars()
+                                .map(|c| match c {
+                                    '*' | '?' => matched.next().cloned().unwrap_or_default(),
+                                    c => c.to_string(),
+                                })
+                                .collect()
+                        }
+                        None => match path.strip_suffix(".in") {
+                            Some(stem) => format!("{}.out", stem),
+                            None => {
+                                return Err(format!(
+                                    "Cannot find output of {}, use @glob: for output",
+                                    path
+                                ))
+                            }
+                        },
+                    };
+                    Ok(job(
+                        Some(path.clone()),
+                        read_include(base, &path)?,
+                        read_include(base, &output_path)?,
+                    ))
+                })
+                .collect()
+        }
+        Some(Include::File(path)) => Ok(vec![job(
+            Some(path.to_string()),
+            read_include(base, path)?,
+            read_output(output)?,
+        )]),
+        None => Ok(vec![job(
+            None,
+            input.as_bytes().to_vec(),
+            read_output(output)?,
+        )]),
+    }
+}
+
+/// Jobs of an input in Pal config of type "simple"
+pub(crate) fn pal_jobs(input: &str, base: &Path) -> Result<Vec<Job>, String> {
+    let (invocation, input) = Invocation::parse(input)?;
+    let inputs = match Include::parse(input) {
+        Some(Include::File(path)) => vec![(Some(path.to_string()), read_include(base, path)?)],
+        Some(Include::Glob(pattern)) => glob_files(base, pattern)?
+            .into_iter()
+            .map(|(path, _)| Ok((Some(path.clone()), read_include(base, &path)?)))
+            .collect::<Result<_, String>>()?,
+        None => vec![(None, input.as_bytes().to_vec())],
+    };
+    Ok(inputs
+        .into_iter()
+        .map(|(name, input)| {
+            invocation.apply(Job {
+                id: 0,
+                input,
+                name,
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+fn read_include(base: &Path, path: &str) -> Result<Vec<u8>, String> {
+    fs::read(base.join(path)).map_err(|e| format!("Cannot read {}: {}", path, e))
+}
+
+/// Files matching `pattern` relative to `base`, with text matched by each wildcard. Wildcards are
+/// only supported in file name, files are sorted by name with numbers compared by value.
+fn glob_files(base: &Path, pattern: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let (dir, file_pattern) = match pattern.rsplit_once('/') {
+        Some((dir, file_pattern)) => (Some(dir), file_pattern),
+        None => (None, pattern),
+    };
+    if dir.is_some_and(|dir| dir.contains(['*', '?'])) {
+        return Err(format!(
+            "Wildcards are only supported in file name: {}",
+            pattern
+        ));
+    }
+    let regex = file_pattern
+        .chars()
+        .map(|c| match c {
+            '*' => String::from("(.*)"),
+            '?' => String::from("(.)"),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect::<String>();
+    let regex = Regex::new(&format!("^{}$", regex)).unwrap();
+
+    let dir_path = base.join(dir.unwrap_or("."));
+    let entries = fs::read_dir(&dir_path)
+        .map_err(|e| format!("Cannot read directory {}: {}", dir.unwrap_or("."), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Cannot read directory entry: {}", e))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_file() {
+            continue;
+        }
+        if let Some(captures) = regex.captures(&file_name) {
+            let matched = captures
+                .iter()
+                .skip(1)
+                .map(|m| m.map_or("", |m| m.as_str()).to_string())
+                .collect();
+            let path = match dir {
+                Some(dir) => format!("{}/{}", dir, file_name),
+                None => file_name,
+            };
+            files.push((path, matched));
+        }
+    }
+    if files.is_empty() {
+        return Err(format!("No file matches {}", pattern));
+    }
+    files.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+    Ok(files)
+}
+
+/// Compare names with numbers in them by value, so `2.in` comes before `10.in`
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    /// Split into runs of digits and other characters
+    fn chunks(s: &str) -> Vec<&str> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let bytes = s.as_bytes();
+        for i in 1..=bytes.len() {
+            if i == bytes.len() || bytes[i].is_ascii_digit() != bytes[start].is_ascii_digit() {
+                chunks.push(&s[start..i]);
+                start = i;
             }
         }
-        x => return Err(ParseError::UnkownInputType(x.to_string())),
+        chunks
     }
-    Ok(result)
+
+    for (a, b) in chunks(a).into_iter().zip(chunks(b)) {
+        let ordering = if a.as_bytes()[0].is_ascii_digit() && b.as_bytes()[0].is_ascii_digit() {
+            let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        } else {
+            a.cmp(b)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.cmp(b)
 }
 
-fn parse_random_pal(input: &str) -> Result<Vec<Job>, ParseError> {
-    let mut result = Vec::new();
+/// Get path of generator source from Pal test config of type "generator"
+pub fn parse_generator_source(input: &str) -> Option<String> {
+    let mut input_lines = input.lines();
+    match input_lines.next() {
+        Some("generator") => input_lines.next().map(|line| line.trim().to_string()),
+        _ => None,
+    }
+}
+
+fn parse_random_pal(input: &str) -> Result<Vec<ConfigBlock>, ParseError> {
+    parse_random_pal_inputs(input)?
+        .into_iter()
+        .map(|(block, label, count)| {
+            block.check()?;
+            Ok(ConfigBlock::new(block.line, BlockJobs::Random(block, count)).with_label(label))
+        })
+        .collect()
+}
+
+/// Generate only the job with given id from RandomPal test config, same as the one from `parse`
+pub fn parse_random_pal_job(input: &str, seed: u64, id: usize) -> Result<Job, ParseError> {
+    random_pal_job(&parse_random_pal_inputs(input)?, seed, id)
+}
+
+/// Generate only the job with given id from unexpanded RandomPal inputs. Ids past number of
+/// tests wrap around the inputs they are generated from, as jobs of `rpal stress` do
+pub fn random_pal_job(blocks: &[RandomInput], seed: u64, id: usize) -> Result<Job, ParseError> {
+    let tests_num: usize = blocks.iter().map(|(_, _, count)| count).sum();
+
+    match block_of_job(blocks, id.checked_rem(tests_num).unwrap_or(id)) {
+        Some((block, label)) => {
+            block.check()?;
+            Ok(label.apply(random_job(block, seed, id)?))
+        }
+        None => Err(ParseError::FormatError(format!(
+            "Job id {} out of range, there are {} tests",
+            id, tests_num
+        ))),
+    }
+}
+
+/// Input which job `id` is generated from, jobs of each input have consecutive ids
+pub fn block_of_job(blocks: &[RandomInput], id: usize) -> Option<(&Block, &Label)> {
+    let mut first_id = 0;
+    blocks.iter().find_map(|(block, label, count)| {
+        first_id += count;
+        (id < first_id).then_some((block, label))
+    })
+}
+
+fn random_job(block: &Block, seed: u64, id: usize) -> Result<Job, ParseError> {
+    let seed = job_seed(seed, id);
+    let input = expand_random(block.input.clone(), &mut ChaCha8Rng::seed_from_u64(seed))
+        .map_err(|e| e.at(block.line, 1))?;
+    expanded_job(id, Some(seed), &input).map_err(|e| ParseError::FormatError(e).at(block.line, 1))
+}
+
+/// Job of an input expanded from glob or random patterns, which may start with arguments and
+/// environment variables expanded as well
+fn expanded_job(id: usize, seed: Option<u64>, input: &str) -> Result<Job, String> {
+    let (invocation, input) = Invocation::parse(input)?;
+    Ok(invocation.apply(Job {
+        id,
+        input: input.as_bytes().to_vec(),
+        seed,
+        ..Default::default()
+    }))
+}
+
+/// Derive seed of a job from master seed and job id(SplitMix64)
+pub fn job_seed(seed: u64, id: usize) -> u64 {
+    let mut z = seed.wrapping_add((id as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Unexpanded RandomPal input with its label and number of tests
+pub type RandomInput = (Block, Label, usize);
+
+/// Parse RandomPal test config into unexpanded inputs, with number of tests of each input
+pub fn parse_random_pal_inputs(input: &str) -> Result<Vec<RandomInput>, ParseError> {
     let mut input_lines = input.lines();
     let tests_num = input_lines.next().ok_or_else(|| {
-        ParseError::UnexpectedEOF(String::from("Input ends when reading tests num."))
+        ParseError::UnexpectedEOF(String::from("Input ends when reading tests num.")).at(1, 1)
     })?;
 
     let tests_num: usize = tests_num.parse().map_err(|e| {
-        ParseError::FormatError(format!("Cannot parse tests num({}): {:?}", tests_num, e))
+        ParseError::FormatError(format!("Cannot parse tests num({}): {:?}", tests_num, e)).at(1, 1)
     })?;
 
-    let mut raw_inputs = Vec::new();
     let separator = input_lines.next().ok_or_else(|| {
-        ParseError::UnexpectedEOF(String::from("Input ends when reading separator."))
+        ParseError::UnexpectedEOF(String::from("Input ends when reading separator.")).at(2, 1)
     })?;
-    let mut this_input = Vec::new();
 
-    for line in input_lines {
-        if line != separator {
-            let mut line = String::from(line);
-            line.push('\n');
-            this_input.push(line);
+    let blocks = split_blocks(input_lines, separator, 3);
+    if blocks.is_empty() {
+        return Err(ParseError::FormatError(String::from(
+            "No input defined in test config, input should end with separator.",
+        ))
+        .at(3, 1));
+    }
+
+    let (labels, shares): (Vec<Label>, Vec<Share>) = blocks
+        .iter()
+        .map(|block| {
+            Label::parse(&block.annotation)
+                .and_then(|(share, label)| Ok((label, Share::parse(share)?)))
+                .map_err(|e| ParseError::FormatError(e).at(block.end_line(), separator.len() + 2))
+        })
+        .collect::<Result<Vec<(Label, Share)>, ParseError>>()?
+        .into_iter()
+        .unzip();
+    let counts = allocate(tests_num, &shares).map_err(|e| ParseError::FormatError(e).at(1, 1))?;
+
+    Ok(blocks
+        .into_iter()
+        .zip(labels)
+        .zip(counts)
+        .map(|((block, label), count)| (block, label, count))
+        .collect())
+}
+
+/// Number of tests of a RandomPal input, written after the separator ending it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Share {
+    /// Tests left are shared evenly by inputs without annotation
+    Even,
+    /// `30%`: percentage of all tests
+    Percent(u128),
+    /// `x500`: exact number of tests
+    Count(usize),
+}
+
+impl Share {
+    pub(crate) fn parse(annotation: &str) -> Result<Share, String> {
+        let invalid = || {
+            format!(
+                "Invalid number of tests: {}, should be like 30% or x500",
+                annotation
+            )
+        };
+        if annotation.is_empty() {
+            Ok(Share::Even)
+        } else if let Some(percent) = annotation.strip_suffix('%') {
+            match percent.trim().parse() {
+                Ok(percent) if percent <= 100 => Ok(Share::Percent(percent)),
+                _ => Err(invalid()),
+            }
+        } else if let Some(count) = annotation.strip_prefix('x') {
+            count
+                .trim()
+                .parse()
+                .map(Share::Count)
+                .map_err(|_| invalid())
         } else {
-            raw_inputs.push(this_input.concat());
-            this_input.clear();
+            Err(invalid())
         }
     }
+}
 
-    let mut this_id = 0;
+/// Split `tests_num` tests by shares of inputs, so that they add up to exactly `tests_num`.
+/// Percentages are of all tests, and scaled to fill up tests left if every other input has
+/// exact number of tests. Fractions are rounded by largest remainder.
+pub(crate) fn allocate(tests_num: usize, shares: &[Share]) -> Result<Vec<usize>, String> {
+    let fixed: usize = shares
+        .iter()
+        .map(|share| match share {
+            Share::Count(count) => *count,
+            _ => 0,
+        })
+        .sum();
+    let percent: u128 = shares
+        .iter()
+        .map(|share| match share {
+            Share::Percent(percent) => *percent,
+            _ => 0,
+        })
+        .sum();
+    let even = shares.iter().filter(|share| **share == Share::Even).count() as u128;
 
-    let jobs_per_input = tests_num / raw_inputs.len();
+    let remaining = tests_num.checked_sub(fixed).ok_or_else(|| {
+        format!(
+            "Inputs with exact number of tests need {} tests, but there are only {} tests",
+            fixed, tests_num
+        )
+    })? as u128;
+    if percent > 100 {
+        return Err(format!("Percentages of inputs add up to {}%", percent));
+    }
 
-    for raw_input in raw_inputs {
-        for _ in 0..jobs_per_input {
-            result.push(Job {
-                id: this_id,
-                input: expand_random(raw_input.clone()).as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
-            });
-            this_id += 1;
+    // Number of tests of each input is `numerator / denominator`
+    let tests_num = tests_num as u128;
+    let (numerators, denominator): (Vec<u128>, u128) = if even > 0 {
+        let even_tests = (remaining * 100)
+            .checked_sub(tests_num * percent)
+            .ok_or_else(|| {
+                format!(
+                    "Inputs with percentages need {}% of {} tests, but only {} tests are left",
+                    percent, tests_num, remaining
+                )
+            })?;
+        let numerators = shares
+            .iter()
+            .map(|share| match share {
+                Share::Even => even_tests,
+                Share::Percent(percent) => tests_num * percent * even,
+                Share::Count(count) => *count as u128 * 100 * even,
+            })
+            .collect();
+        (numerators, 100 * even)
+    } else if percent > 0 {
+        let numerators = shares
+            .iter()
+            .map(|share| match share {
+                Share::Percent(p) => remaining * p,
+                Share::Count(count) => *count as u128 * percent,
+                Share::Even => unreachable!(),
+            })
+            .collect();
+        (numerators, percent)
+    } else if remaining > 0 {
+        return Err(format!(
+            "Numbers of tests of inputs add up to {}, but there are {} tests",
+            fixed, tests_num
+        ));
+    } else {
+        return Ok(shares
+            .iter()
+            .map(|share| match share {
+                Share::Count(count) => *count,
+                _ => 0,
+            })
+            .collect());
+    };
+
+    let mut counts: Vec<u128> = numerators.iter().map(|n| n / denominator).collect();
+    let mut left = tests_num - counts.iter().sum::<u128>();
+    let mut order: Vec<usize> = (0..shares.len()).collect();
+    // Stable sort, so earlier inputs get extra tests first when remainders are equal
+    order.sort_by_key(|&i| std::cmp::Reverse(numerators[i] % denominator));
+    for i in order {
+        if left == 0 {
+            break;
+        }
+        if numerators[i] % denominator > 0 {
+            counts[i] += 1;
+            left -= 1;
         }
     }
+    Ok(counts.into_iter().map(|count| count as usize).collect())
+}
 
-    Ok(result)
+/// Expand every combination of patterns. Variables of directives are enumerated too, while
+/// other directives are expanded randomly with `rng`.
+pub fn expand_glob<R: Rng>(input: String, rng: R) -> Result<Vec<String>, ParseError> {
+    GlobExpansion::new(input, rng).collect()
 }
 
-pub fn expand_glob(input: String) -> Vec<String> {
-    let mut expanded = vec![input];
-    loop {
-        let before_len = expanded.len();
-        expanded = expanded.into_iter().flat_map(|s| expand_one(s)).collect();
-        let after_len = expanded.len();
+/// Every combination of patterns in an input, generated lazily
+pub struct GlobExpansion<R: Rng> {
+    variants: Variants,
+    rng: R,
+    combinations: Option<Combinations>,
+}
 
-        if before_len == after_len {
-            break;
+impl<R: Rng> GlobExpansion<R> {
+    pub fn new(input: String, rng: R) -> GlobExpansion<R> {
+        GlobExpansion {
+            variants: enumerate_variables(input),
+            rng,
+            combinations: None,
         }
     }
+}
+
+impl<R: Rng> Iterator for GlobExpansion<R> {
+    type Item = Result<String, ParseError>;
 
-    expanded
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(input) = self.combinations.as_mut().and_then(|c| c.next()) {
+                return Some(Ok(input));
+            }
+            let combinations = self.variants.next()?.and_then(|(input, variables)| {
+                let input = expand_directives(input, variables, &mut self.rng)?;
+                Combinations::new(&input)
+            });
+            match combinations {
+                Ok(combinations) => self.combinations = Some(combinations),
+                Err(e) => return Some(Err(ParseError::FormatError(e))),
+            }
+        }
+    }
 }
 
-fn expand_one(input: String) -> Vec<String> {
-    let mut result = Vec::new();
-    // First capture pattern like: [1-9]
-    let first_re = Regex::new(r"\[(\d+)-(\d+)\]").unwrap();
-    match first_re.captures(&input) {
-        Some(cap) => {
-            let full_match = cap
-                .get(0)
-                .expect("cap[0] is guaranteed to have non-None value")
-                .as_str();
-            let begin_number: usize = cap
-                .get(1)
-                .expect("Group 1(Begin number) should exist.")
-                .as_str()
-                .parse()
-                .expect("Group 1(Begin number) should be number.");
-            let end_number: usize = cap
-                .get(2)
-                .expect("Group 2(End number) should exist.")
-                .as_str()
-                .parse()
-                .expect("Group 2(End number) should be number.");
-            for i in begin_number..=end_number {
-                result.push(input.replace(full_match, &i.to_string()));
-            }
-        }
-        None => {
-            // If first pattern not found, try [abc]、[abc123kkk]
-            let then_re = Regex::new(r"[^\\](\[(.+?[^\\])\])").unwrap();
-            match then_re.captures(&input) {
-                Some(cap) => {
-                    let full_match = cap
-                        .get(1)
-                        .expect("Group 1(Full match) should exist.")
-                        .as_str();
-                    let inner = cap.get(2).expect("Group 2(Inner) should exist.").as_str();
-
-                    inner
-                        .chars()
-                        .into_iter()
-                        .filter(|c| *c != '\\')
-                        .for_each(|c| result.push(input.replace(full_match, &c.to_string())));
-                }
-                None => {
-                    result.push(input);
-                }
+/// Odometer over values of patterns in an input, the leftmost pattern changes slowest
+struct Combinations {
+    /// Text around patterns, one more than patterns
+    pieces: Vec<String>,
+    patterns: Vec<Pattern>,
+    indices: Option<Vec<u128>>,
+}
+
+impl Combinations {
+    fn new(input: &str) -> Result<Combinations, String> {
+        let mut pieces = Vec::new();
+        let mut patterns = Vec::new();
+        let mut from = 0;
+        while let Some((begin, end, pattern)) = find_pattern(input, from).map_err(|(_, e)| e)? {
+            pieces.push(input[from..begin].to_string());
+            patterns.push(pattern);
+            from = end;
+        }
+        pieces.push(input[from..].to_string());
+        Ok(Combinations {
+            indices: Some(vec![0; patterns.len()]),
+            pieces,
+            patterns,
+        })
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let indices = self.indices.as_mut()?;
+        let mut result = self.pieces[0].clone();
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            result.push_str(&pattern.value(indices[i]));
+            result.push_str(&self.pieces[i + 1]);
+        }
+
+        // Advance, from the rightmost pattern
+        let mut i = self.patterns.len();
+        loop {
+            if i == 0 {
+                self.indices = None;
+                break;
+            }
+            i -= 1;
+            indices[i] += 1;
+            if indices[i] < self.patterns[i].count() {
+                break;
             }
+            indices[i] = 0;
         }
+        Some(result)
     }
-    result
 }
 
-fn expand_random(mut input: String) -> String {
-    // First capture pattern like: [1-9]
-    let first_re = Regex::new(r"\[(\d+)-(\d+)\]").unwrap();
-    let _input = input.clone();
-    first_re.captures_iter(&_input).for_each(|cap| {
-        let full_match = cap
-            .get(0)
-            .expect("cap[0] is guaranteed to have non-None value")
-            .as_str();
-        let begin_number: usize = cap
-            .get(1)
-            .expect("Group 1(Begin number) should exist.")
-            .as_str()
-            .parse()
-            .expect("Group 1(Begin number) should be number.");
-        let end_number: usize = cap
-            .get(2)
-            .expect("Group 2(End number) should exist.")
-            .as_str()
-            .parse()
-            .expect("Group 2(End number) should be number.");
-        input = input.replace(
-            full_match,
-            &thread_rng()
-                .gen_range(begin_number..=end_number)
-                .to_string(),
+/// Number of combinations of a glob input, counting stops once it's more than `cap`
+fn count_glob(block: &Block, cap: u128) -> Result<u128, ParseError> {
+    let mut total = 0u128;
+    for variant in enumerate_variables(block.input.clone()) {
+        let (input, variables) =
+            variant.map_err(|e| ParseError::FormatError(e).at(block.line, 1))?;
+        let spans = directive_spans(&input, &variables)
+            .map_err(|(_, e)| ParseError::FormatError(e).at(block.line, 1))?;
+
+        // Patterns in directives are expanded by directives, not enumerated
+        let mut product = 1u128;
+        let mut from = 0;
+        let gaps = spans
+            .iter()
+            .map(|&(begin, end)| {
+                let gap = &input[from..begin];
+                from = end;
+                gap
+            })
+            .collect::<Vec<&str>>();
+        for gap in gaps.into_iter().chain(std::iter::once(&input[from..])) {
+            let mut gap_from = 0;
+            while let Some((_, end, pattern)) = find_pattern(gap, gap_from)
+                .map_err(|(_, e)| ParseError::FormatError(e).at(block.line, 1))?
+            {
+                product = product.saturating_mul(pattern.count());
+                gap_from = end;
+            }
+        }
+
+        total = total.saturating_add(product);
+        if total > cap {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Expand directives and patterns randomly
+pub fn expand_random<R: Rng>(input: String, rng: &mut R) -> Result<String, ParseError> {
+    let input = expand_directives(input, Variables::new(), rng).map_err(ParseError::FormatError)?;
+    expand_random_patterns(input, rng).map_err(ParseError::FormatError)
+}
+
+/// Expand every pattern to a random value, directives should be expanded already
+pub(crate) fn expand_random_patterns<R: Rng>(
+    mut input: String,
+    rng: &mut R,
+) -> Result<String, String> {
+    let mut from = 0;
+    while let Some((begin, end, pattern)) = find_pattern(&input, from).map_err(|(_, e)| e)? {
+        let value = pattern.random_value(rng);
+        input.replace_range(begin..end, &value);
+        from = begin + value.len();
+    }
+    Ok(input)
+}
+
+/// A `[...]` in input
+#[derive(Debug, PartialEq, Eq)]
+enum Pattern {
+    /// `[-5-5]`, `[0-100:10]`
+    Integers { begin: i128, end: i128, step: u128 },
+    /// Range with bound beyond 128 bits
+    BigIntegers {
+        begin: BigInt,
+        end: BigInt,
+        step: BigUint,
+    },
+    /// `[abc]`, `[a-z0-9_]`
+    Chars(Vec<char>),
+}
+
+impl Pattern {
+    fn parse(inner: &str) -> Result<Pattern, String> {
+        static INTEGERS_RE: OnceLock<Regex> = OnceLock::new();
+        let integers_re =
+            INTEGERS_RE.get_or_init(|| Regex::new(r"^(-?\d+)-(-?\d+)(?::(\d+))?$").unwrap());
+        let cap = match integers_re.captures(inner) {
+            Some(cap) => cap,
+            None => return Ok(Pattern::Chars(parse_char_set(inner)?)),
+        };
+        let step = cap.get(3).map_or("1", |m| m.as_str());
+        if let (Ok(begin), Ok(end), Ok(step)) = (
+            cap[1].parse::<i128>(),
+            cap[2].parse::<i128>(),
+            step.parse::<u128>(),
+        ) {
+            if begin > end || step == 0 {
+                return Err(format!("Invalid range: [{}]", inner));
+            }
+            return Ok(Pattern::Integers { begin, end, step });
+        }
+        let (begin, end, step): (BigInt, BigInt, BigUint) = (
+            cap[1].parse().unwrap(),
+            cap[2].parse().unwrap(),
+            step.parse().unwrap(),
         );
-    });
+        if begin > end || step == BigUint::ZERO {
+            return Err(format!("Invalid range: [{}]", inner));
+        }
+        Ok(Pattern::BigIntegers { begin, end, step })
+    }
+
+    /// Number of values, saturated at `u128::MAX`
+    fn count(&self) -> u128 {
+        match self {
+            Pattern::Integers { begin, end, step } => {
+                ((*end as u128).wrapping_sub(*begin as u128) / step).saturating_add(1)
+            }
+            Pattern::BigIntegers { begin, end, step } => {
+                u128::try_from((end - begin).magnitude() / step + 1u32).unwrap_or(u128::MAX)
+            }
+            Pattern::Chars(chars) => chars.len() as u128,
+        }
+    }
 
-    // then [abc]、[abc123kkk]
-    let then_re = Regex::new(r"[^\\](\[(.+?[^\\])\])").unwrap();
-    let _input = input.clone();
-    then_re.captures_iter(&_input).for_each(|cap| {
-        let full_match = cap
-            .get(1)
-            .expect("Group 1(Full match) should exist.")
-            .as_str();
-        let inner = cap.get(2).expect("Group 2(Inner) should exist.").as_str();
+    /// The `k`th value, in ascending order
+    fn value(&self, k: u128) -> String {
+        match self {
+            Pattern::Integers { begin, step, .. } => {
+                ((*begin as u128).wrapping_add(k * step) as i128).to_string()
+            }
+            Pattern::BigIntegers { begin, step, .. } => {
+                (begin + BigInt::from(BigUint::from(k) * step)).to_string()
+            }
+            Pattern::Chars(chars) => chars[k as usize].to_string(),
+        }
+    }
 
-        let inner_list: Vec<char> = inner.chars().into_iter().filter(|c| *c != '\\').collect();
+    fn random_value<R: Rng>(&self, rng: &mut R) -> String {
+        match self {
+            Pattern::Integers { begin, end, step } => {
+                let count = (*end as u128).wrapping_sub(*begin as u128) / step;
+                let k = rng.gen_range(0..=count);
+                ((*begin as u128).wrapping_add(k * step) as i128).to_string()
+            }
+            Pattern::BigIntegers { begin, end, step } => {
+                let count = (end - begin).magnitude() / step;
+                let k = rng.gen_biguint_range(&BigUint::ZERO, &(count + 1u32));
+                (begin + BigInt::from(k * step)).to_string()
+            }
+            Pattern::Chars(chars) => chars.choose(rng).unwrap().to_string(),
+        }
+    }
+}
 
-        let choosed = inner_list
-            .choose(&mut thread_rng())
-            .expect("Inner list length should > 0");
+/// Find next pattern at or after `from`, returns its span(including brackets) and content.
+/// `\[` outside of pattern is left as is, and `[` without matching `]` is not a pattern.
+fn find_pattern(input: &str, from: usize) -> OffsetResult<Option<(usize, usize, Pattern)>> {
+    let bytes = input.as_bytes();
+    let mut i = from;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'[' => {
+                // Find unescaped `]`
+                let mut j = i + 1;
+                while j < bytes.len() && bytes[j] != b']' {
+                    j += if bytes[j] == b'\\' { 2 } else { 1 };
+                }
+                if j >= bytes.len() {
+                    return Ok(None);
+                }
+                if j > i + 1 {
+                    let pattern = Pattern::parse(&input[i + 1..j]).map_err(|e| (i, e))?;
+                    return Ok(Some((i, j + 1, pattern)));
+                }
+                i = j + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    Ok(None)
+}
 
-        input = input.replace(full_match, &choosed.to_string());
-    });
-    input
+/// Check every pattern in input, returns byte offset of the invalid one on error
+pub(crate) fn check_patterns(input: &str) -> OffsetResult<()> {
+    let mut from = 0;
+    while let Some((_, end, _)) = find_pattern(input, from)? {
+        from = end;
+    }
+    Ok(())
+}
+
+/// Parse a set of characters like `abc`, `a-z0-9`. Backslash escapes the next character, so
+/// `\-`, `\]` and `\\` are `-`, `]` and `\`.
+pub(crate) fn parse_char_set(s: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut set = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() {
+            set.push(chars[i + 1]);
+            i += 2;
+        } else if i + 2 < chars.len() && chars[i + 1] == '-' {
+            if chars[i] > chars[i + 2] {
+                return Err(format!(
+                    "Invalid character range: {}-{}",
+                    chars[i],
+                    chars[i + 2]
+                ));
+            }
+            set.extend(chars[i]..=chars[i + 2]);
+            i += 3;
+        } else {
+            set.push(chars[i]);
+            i += 1;
+        }
+    }
+    if set.is_empty() {
+        return Err(String::from("Character set is empty"));
+    }
+    Ok(set)
 }
 
 #[cfg(test)]
@@ -317,14 +1579,14 @@ mod tests {
     #[test]
     fn test_parse_check() {
         let input = "----\naaabbbcccddd\neeefffggghhh\n----\naaabbbcccddd\neeefffggghhh\n----\naaabbbcccddd\neeefffggghhh\n----\naaabbbcccddd\neeefffggghhh\n----";
-        let pal_list = parse(&PalType::Check, input).unwrap();
+        let pal_list = parse(&PalType::Check, input, 0).unwrap();
         assert_eq!(
             pal_list[0],
             Job {
                 id: 0,
                 input: "aaabbbcccddd\neeefffggghhh\n".as_bytes().to_vec(),
                 expected_output: "aaabbbcccddd\neeefffggghhh\n".as_bytes().to_vec(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -333,7 +1595,7 @@ mod tests {
                 id: 1,
                 input: "aaabbbcccddd\neeefffggghhh\n".as_bytes().to_vec(),
                 expected_output: "aaabbbcccddd\neeefffggghhh\n".as_bytes().to_vec(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
     }
@@ -341,14 +1603,13 @@ mod tests {
     #[test]
     fn test_parse_pal_simple() {
         let input = "simple\n----\naaabbb\ncccddd\n----\neeefff\nggghhh\n----\n";
-        let pal_list = parse(&PalType::Pal, input).unwrap();
+        let pal_list = parse(&PalType::Pal, input, 0).unwrap();
         assert_eq!(
             pal_list[0],
             Job {
                 id: 0,
                 input: "aaabbb\ncccddd\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -356,16 +1617,81 @@ mod tests {
             Job {
                 id: 1,
                 input: "eeefff\nggghhh\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
     }
 
+    #[test]
+    fn test_parse_pal_generator() {
+        let input = "generator\ngen.c\n3\n";
+        let pal_list = parse(&PalType::Pal, input, 0).unwrap();
+        assert_eq!(pal_list.len(), 3);
+        assert_eq!(pal_list[2].id, 2);
+        assert!(pal_list.iter().all(|job| job.seed.is_some()));
+        assert_eq!(parse_generator_source(input), Some(String::from("gen.c")));
+        assert_eq!(parse_generator_source("simple\n----\n"), None);
+    }
+
+    #[test]
+    fn test_parse_random_pal_seed() {
+        let input = "10\n----\n[1-1000000] [abcdef]\n----\n[1-5]\n----\n";
+        let pal_list = parse(&PalType::RandomPal, input, 42).unwrap();
+        assert_eq!(pal_list, parse(&PalType::RandomPal, input, 42).unwrap());
+        assert_ne!(pal_list, parse(&PalType::RandomPal, input, 43).unwrap());
+        assert_eq!(parse_random_pal_job(input, 42, 7).unwrap(), pal_list[7]);
+        // Jobs past number of tests, like those of stress test, are generated from inputs of
+        // jobs they wrap around to
+        let job = parse_random_pal_job(input, 42, 17).unwrap();
+        assert_eq!(job.id, 17);
+        assert_eq!(job.seed, Some(job_seed(42, 17)));
+        assert!(job.input.ends_with(b"\n") && job.input.len() <= 2);
+        assert!(parse_random_pal_job("0\n----\n1\n----\n", 42, 0).is_err());
+    }
+
+    #[test]
+    fn test_random_pal_allocation() {
+        let counts = |input: &str| {
+            parse_random_pal_inputs(input).map(|blocks| {
+                blocks
+                    .into_iter()
+                    .map(|(_, _, count)| count)
+                    .collect::<Vec<usize>>()
+            })
+        };
+        assert_eq!(
+            counts("10\n---\na\n---\nb\n---\nc\n---\n").unwrap(),
+            [4, 3, 3]
+        );
+        assert_eq!(
+            counts("1000\n---\na\n--- 30%\nb\n--- x500\nc\n---\n").unwrap(),
+            [300, 500, 200]
+        );
+        assert_eq!(
+            counts("7\n---\na\n--- 50%\nb\n--- 50%\nc\n--- x2\n").unwrap(),
+            [3, 2, 2]
+        );
+        assert_eq!(counts("0\n---\na\n---\nb\n---\n").unwrap(), [0, 0]);
+
+        assert!(counts("10\n---\na\n--- x20\nb\n---\n").is_err());
+        assert!(counts("10\n---\na\n--- x5\nb\n--- x4\n").is_err());
+        assert!(counts("10\n---\na\n--- 60%\nb\n--- 50%\n").is_err());
+        assert!(counts("10\n---\na\n--- x8\nb\n--- 50%\nc\n---\n").is_err());
+        assert!(counts("10\n---\na\n--- half\n").is_err());
+
+        let input = "10\n---\na\n--- 25%\nb\n---\nc\n---\n";
+        let jobs = parse(&PalType::RandomPal, input, 0).unwrap();
+        assert_eq!(jobs.len(), 10);
+        assert_eq!(parse_random_pal_job(input, 0, 9).unwrap(), jobs[9]);
+        assert_eq!(jobs[1].input, b"a\n");
+        assert_eq!(jobs[2].input, b"b\n");
+        assert!(parse(&PalType::Pal, "simple\n---\na\n--- x2\n", 0).is_err());
+    }
+
     #[test]
     fn test_expand_glob_simple() {
         let raw_input = String::from("[1-3]bc[abc145]");
-        let output = expand_glob(raw_input);
+        let output = expand_glob(raw_input, ChaCha8Rng::seed_from_u64(0)).unwrap();
 
         assert_eq!(
             output,
@@ -395,7 +1721,7 @@ mod tests {
     #[test]
     fn test_expand_glob_do_not_touch_escape_character() {
         let raw_input = String::from(r"[1-3]abc\[1-3\]kkk\[abc\]");
-        let output = expand_glob(raw_input);
+        let output = expand_glob(raw_input, ChaCha8Rng::seed_from_u64(0)).unwrap();
 
         assert_eq!(
             output,
@@ -407,10 +1733,28 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_literal_braces() {
+        // Braces not forming a directive stay in input, and don't fail checking
+        let config = "glob\n---\n{treehouse} int main() { x = 1; } [1-2]\n---\n";
+        let inputs: Vec<Vec<u8>> = parse(&PalType::Pal, config, 0)
+            .unwrap()
+            .into_iter()
+            .map(|job| job.input)
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                b"{treehouse} int main() { x = 1; } 1\n".to_vec(),
+                b"{treehouse} int main() { x = 1; } 2\n".to_vec(),
+            ]
+        );
+    }
+
     #[test]
     fn test_expand_glob_escaped_dash() {
         let raw_input = String::from(r"[1-3]abc[123\-456]");
-        let output = expand_glob(raw_input);
+        let output = expand_glob(raw_input, ChaCha8Rng::seed_from_u64(0)).unwrap();
 
         assert_eq!(
             output,
@@ -440,18 +1784,326 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_expand_glob_extended_ranges() {
+        let expand = |s: &str| expand_glob(String::from(s), ChaCha8Rng::seed_from_u64(0)).unwrap();
+        assert_eq!(expand("[-2-1]"), vec!["-2", "-1", "0", "1"]);
+        assert_eq!(expand("[0-25:10]"), vec!["0", "10", "20"]);
+        assert_eq!(expand("[a-c_]"), vec!["a", "b", "c", "_"]);
+        assert_eq!(expand(r"[a\-c]"), vec!["a", "-", "c"]);
+        assert_eq!(expand("[1-2][1-2]"), vec!["11", "12", "21", "22"]);
+        assert_eq!(
+            expand(
+                "[170141183460469231731687303715884105727-170141183460469231731687303715884105728]"
+            ),
+            vec![
+                "170141183460469231731687303715884105727",
+                "170141183460469231731687303715884105728"
+            ]
+        );
+        assert!(expand_glob(String::from("[5-1]"), ChaCha8Rng::seed_from_u64(0)).is_err());
+        assert!(expand_glob(String::from("[1-5:0]"), ChaCha8Rng::seed_from_u64(0)).is_err());
+    }
+
+    #[test]
+    fn test_expand_random_extended_ranges() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..100 {
+            let value: i128 = expand_random(String::from("[-100-100:5]"), &mut rng)
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((-100..=100).contains(&value) && value % 5 == 0);
+
+            let value: BigInt = expand_random(
+                String::from("[-100000000000000000000000000000000000000000-100000000000000000000000000000000000000000:3]"),
+                &mut rng,
+            )
+            .unwrap()
+            .parse()
+            .unwrap();
+            let bound: BigInt = "100000000000000000000000000000000000000000"
+                .parse()
+                .unwrap();
+            assert!(-&bound <= value && value <= bound);
+            assert_eq!((value + &bound) % 3, BigInt::from(0));
+        }
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let position = |input: &str| match parse(&PalType::Pal, input, 0) {
+            Err(ParseError::Located { line, column, .. }) => (line, column),
+            Err(e) => panic!("Error without position: {:?}", e),
+            Ok(_) => panic!("Should not parse: {}", input),
+        };
+        assert_eq!(position("glob\n---\n1\n---\na [5-1]\n---\n"), (5, 3));
+        assert_eq!(
+            position("glob\n---\n{n=[1-5]}\n{repeat m: x}\n---\n"),
+            (4, 1)
+        );
+        assert_eq!(
+            position("glob\n---\n{lines 2: [1-2] [z-a]}\n---\n"),
+            (3, 17)
+        );
+        assert_eq!(position("glob\n---\n{perm 3: x}\n---\n"), (3, 1));
+        assert_eq!(
+            position("glob\n---\n1 {repeat 100000000: 1}\n---\n"),
+            (3, 3)
+        );
+        assert_eq!(
+            position("glob\n---\n{repeat 10000000: {repeat 10000000: x}}\n---\n"),
+            (3, 19)
+        );
+        assert_eq!(position("unknown\n"), (1, 1));
+        assert_eq!(position("generator\ngen.c\nabc\n"), (3, 1));
+
+        let error = parse(&PalType::RandomPal, "10\n---\n", 0).unwrap_err();
+        assert_eq!(
+            format!("{:?}", error.in_file("a.test")),
+            "a.test:3:1: Format error: No input defined in test config, input should end with separator."
+        );
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        let labels = |pal_type: &PalType, input: &str| {
+            parse(pal_type, input, 0).map(|jobs| {
+                jobs.into_iter()
+                    .map(|job| (job.group, job.name))
+                    .collect::<Vec<_>>()
+            })
+        };
+        let label =
+            |group: &str, name: Option<&str>| (Some(group.to_string()), name.map(str::to_string));
+
+        assert_eq!(
+            labels(
+                &PalType::Check,
+                "---\n1\n--- [edge: n=0]\n1\n---\n2\n---\n2\n---\n"
+            )
+            .unwrap(),
+            [label("edge", Some("n=0")), (None, None)]
+        );
+        assert_eq!(
+            labels(&PalType::Pal, "simple\n---\n1\n--- [small]\n2\n---\n").unwrap(),
+            [label("small", None), (None, None)]
+        );
+        assert_eq!(
+            labels(&PalType::Pal, "glob\n---\n[1-2]\n--- [small: one digit]\n").unwrap(),
+            [
+                label("small", Some("one digit")),
+                label("small", Some("one digit"))
+            ]
+        );
+        let input = "3\n---\n[1-9]\n--- x2 [small]\n[10-99]\n--- [large]\n";
+        assert_eq!(
+            labels(&PalType::RandomPal, input).unwrap(),
+            [
+                label("small", None),
+                label("small", None),
+                label("large", None)
+            ]
+        );
+        assert_eq!(
+            parse_random_pal_job(input, 0, 2).unwrap().group.as_deref(),
+            Some("large")
+        );
+
+        assert!(parse(&PalType::Check, "---\n1\n---\n1\n--- [edge]\n", 0).is_err());
+        assert!(parse(&PalType::Pal, "simple\n---\n1\n--- []\n", 0).is_err());
+        assert!(parse(&PalType::Pal, "simple\n---\n1\n--- x2 [small]\n", 0).is_err());
+
+        // Lines starting with separator but no annotation are input
+        let inputs = |pal_type: &PalType, input: &str| {
+            parse(pal_type, input, 0)
+                .unwrap()
+                .into_iter()
+                .map(|job| String::from_utf8(job.input).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            inputs(&PalType::Pal, "simple\n---\n1\n--- note\n---x\n--- \n---\n"),
+            ["1\n--- note\n---x\n--- \n"]
+        );
+        assert_eq!(
+            inputs(&PalType::Check, "---\n1\n--- 2 items\n---\n1\n---\n"),
+            ["1\n--- 2 items\n"]
+        );
+    }
+
+    #[test]
+    fn test_parse_invocation() {
+        let invocations = |pal_type: &PalType, input: &str| {
+            parse(pal_type, input, 0).map(|jobs| {
+                jobs.into_iter()
+                    .map(|job| (job.args, job.env, String::from_utf8(job.input).unwrap()))
+                    .collect::<Vec<_>>()
+            })
+        };
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let env = |key: &str, value: &str| vec![(key.to_string(), value.to_string())];
+
+        let check = "---\n@args: -n \"two words\" ''\n@env: LANG=C\n1\n---\n1\n---\n";
+        assert_eq!(
+            invocations(&PalType::Check, check).unwrap(),
+            [(
+                args(&["-n", "two words", ""]),
+                env("LANG", "C"),
+                String::from("1\n")
+            )]
+        );
+        assert_eq!(
+            invocations(&PalType::Pal, "glob\n---\n@args: --size=[1-2]\n3\n---\n").unwrap(),
+            [
+                (args(&["--size=1"]), Vec::new(), String::from("3\n")),
+                (args(&["--size=2"]), Vec::new(), String::from("3\n")),
+            ]
+        );
+        let jobs = invocations(
+            &PalType::RandomPal,
+            "2\n---\n@env: SEED=[5-5]\n[1-9]\n---\n",
+        );
+        assert!(jobs
+            .unwrap()
+            .iter()
+            .all(|(args, env, input)| args.is_empty()
+                && env == &vec![(String::from("SEED"), String::from("5"))]
+                && input.len() == 2));
+
+        assert!(parse(&PalType::Pal, "simple\n---\n@args: \"open\n---\n", 0).is_err());
+        assert!(parse(&PalType::Pal, "simple\n---\n@env: =1\n---\n", 0).is_err());
+    }
+
+    #[test]
+    fn test_include_files() {
+        let base = Path::new("tests/parser/include");
+        let jobs = |pal_type: &PalType, config: &str| {
+            parse_lazy(pal_type, config, base, 0, GlobLimit::UNLIMITED)
+                .and_then(|(_, jobs)| jobs.collect::<Result<Vec<Job>, ParseError>>())
+        };
+        let describe = |jobs: Vec<Job>| {
+            jobs.into_iter()
+                .map(|job| {
+                    (
+                        job.name.unwrap_or_default(),
+                        String::from_utf8(job.input).unwrap(),
+                        String::from_utf8(job.expected_output).unwrap(),
+                    )
+                })
+                .collect::<Vec<(String, String, String)>>()
+        };
+
+        let check = jobs(
+            &PalType::Check,
+            "---\n@glob:cases/*.in\n---\n---\n@file:cases/single.in\n---\n15\n---\n",
+        )
+        .unwrap();
+        assert_eq!(
+            describe(check),
+            [
+                ("cases/1.in", "1 1\n", "2\n"),
+                ("cases/2.in", "2 2\n", "4\n"),
+                ("cases/10.in", "10 10\n", "20\n"),
+                ("cases/single.in", "7 8\n", "15\n"),
+                ("cases/single.in", "7 8\n", "15\n"),
+            ]
+            .map(|(name, input, output)| (name.into(), input.into(), output.into()))
+        );
+
+        let check = jobs(
+            &PalType::Check,
+            "---\n@glob:cases/?.in\n---\n@glob:cases/*.ans\n---\n",
+        )
+        .unwrap();
+        assert_eq!(check.len(), 2);
+        assert_eq!(check[1].expected_output, b"4\n");
+
+        let pal = jobs(&PalType::Pal, "simple\n---\n@glob:cases/1*.in\n---\n").unwrap();
+        assert_eq!(
+            pal.iter()
+                .map(|job| job.name.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("cases/1.in"), Some("cases/10.in")]
+        );
+        assert_eq!(pal[1].id, 1);
+
+        assert!(jobs(&PalType::Check, "---\n@file:cases/none.in\n---\n\n---\n").is_err());
+        assert!(jobs(&PalType::Check, "---\n@glob:cases/*.txt\n---\n---\n").is_err());
+        assert!(jobs(&PalType::Check, "---\n@glob:cases/*\n---\n---\n").is_err());
+        assert_eq!(natural_cmp("a2.in", "a10.in"), Ordering::Less);
+        assert_eq!(natural_cmp("02.in", "1.in"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_glob_limit() {
+        let config = "glob\n---\n[1-1000000000] [1-1000000000]\n---\nx [a-c]\n---\n";
+        assert_eq!(
+            count_blocks(&PalType::Pal, config, Path::new(""), u128::MAX).unwrap(),
+            vec![(3, 1_000_000_000_000_000_000), (5, 3)]
+        );
+        assert_eq!(
+            count_blocks(
+                &PalType::Pal,
+                "glob\n---\n{n=[1-3]}{repeat n: [1-9]}[0-1]\n---\n",
+                Path::new(""),
+                100
+            )
+            .unwrap(),
+            vec![(3, 6)]
+        );
+
+        // Expanded lazily, so the first jobs come without enumerating every combination
+        let (count, jobs) = parse_lazy(
+            &PalType::Pal,
+            config,
+            Path::new(""),
+            0,
+            GlobLimit::UNLIMITED,
+        )
+        .unwrap();
+        assert_eq!(count, 1_000_000_000_000_000_003);
+        let inputs: Vec<Vec<u8>> = jobs.take(2).map(|job| job.unwrap().input).collect();
+        assert_eq!(inputs, vec![b"1 1\n".to_vec(), b"1 2\n".to_vec()]);
+
+        let limit = GlobLimit {
+            max_combinations: 10,
+            sample: false,
+        };
+        assert!(parse_lazy(&PalType::Pal, config, Path::new(""), 0, limit).is_err());
+        assert!(parse_lazy(
+            &PalType::Pal,
+            "glob\n---\n[1-10]\n---\n",
+            Path::new(""),
+            0,
+            limit
+        )
+        .is_ok());
+
+        let sample = GlobLimit {
+            sample: true,
+            ..limit
+        };
+        let (count, jobs) = parse_lazy(&PalType::Pal, config, Path::new(""), 0, sample).unwrap();
+        let jobs: Vec<Job> = jobs.collect::<Result<_, _>>().unwrap();
+        assert_eq!(count, 10);
+        assert_eq!(jobs.len(), 10);
+        assert!(jobs.iter().all(|job| job.seed.is_some()));
+        let (_, again) = parse_lazy(&PalType::Pal, config, Path::new(""), 0, sample).unwrap();
+        assert_eq!(jobs, again.collect::<Result<Vec<Job>, _>>().unwrap());
+    }
+
     #[test]
     fn test_parse_pal() {
         // simple
         let input = "simple\n----\n1\n2\n----\n3\n4\n----\n";
-        let pal_list = parse(&PalType::Pal, input).unwrap();
+        let pal_list = parse(&PalType::Pal, input, 0).unwrap();
         assert_eq!(
             pal_list[0],
             Job {
                 id: 0,
                 input: "1\n2\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -459,21 +2111,19 @@ mod tests {
             Job {
                 id: 1,
                 input: "3\n4\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
 
         // glob
         let input = "glob\n----\n[1-3]abc\n----\nkkk[1-3]\n----\n";
-        let pal_list = parse(&PalType::Pal, input).unwrap();
+        let pal_list = parse(&PalType::Pal, input, 0).unwrap();
         assert_eq!(
             pal_list[0],
             Job {
                 id: 0,
                 input: "1abc\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -481,8 +2131,7 @@ mod tests {
             Job {
                 id: 1,
                 input: "2abc\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -490,8 +2139,7 @@ mod tests {
             Job {
                 id: 2,
                 input: "3abc\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -499,8 +2147,7 @@ mod tests {
             Job {
                 id: 3,
                 input: "kkk1\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -508,8 +2155,7 @@ mod tests {
             Job {
                 id: 4,
                 input: "kkk2\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -517,8 +2163,7 @@ mod tests {
             Job {
                 id: 5,
                 input: "kkk3\n".as_bytes().to_vec(),
-                expected_output: Vec::new(),
-                actual_output: Vec::new(),
+                ..Default::default()
             }
         );
     }
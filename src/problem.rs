@@ -0,0 +1,592 @@
+//! Structured test config, e.g. `problem.toml`, read into the same jobs as test config of
+//! separators. Besides tests, it defines how output is judged and limits of tests.
+use crate::job::{Comparator, FileIo};
+use crate::limits::Limits;
+use crate::pal::PalType;
+use crate::parser::{
+    allocate, check_jobs, pal_jobs, Block, BlockJobs, ConfigBlock, Label, ParseError, Share,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Format of structured test config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProblemFormat {
+    Toml,
+    Yaml,
+}
+
+impl ProblemFormat {
+    /// Format of test config at `path`, detected from its extension
+    pub fn of(path: &Path) -> Option<ProblemFormat> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Problem {
+    /// Number of RandomPal tests, shared by `random` inputs
+    pub tests: Option<usize>,
+    /// How output is compared with expected output, if there is no checker
+    pub comparator: Comparator,
+    /// Limits of every test, time limit is also used if `--timeout` isn't given
+    pub limits: Limits,
+    pub checker: Option<Checker>,
+    pub generator: Option<Generator>,
+    /// Files tests are read from and written to, instead of stdin and stdout
+    pub files: Option<FileIo>,
+    /// Files and directories copied to working directory of every test, relative to test config
+    pub fixtures: Vec<String>,
+    pub cases: Vec<Case>,
+    pub groups: Vec<Group>,
+}
+
+/// Program judging output, run as `checker input output answer result` with paths of files.
+/// Output is accepted if it exits with 0, and is wrong if it exits with 1 or 2. If it exits
+/// with 7, it's partially correct with score written to result file as `points 0.5`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checker {
+    /// Source of checker, relative to test config
+    pub source: String,
+}
+
+/// Program generating input of Pal tests, see test config of type "generator"
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Generator {
+    /// Source of generator, relative to test config
+    pub source: String,
+    pub tests: usize,
+}
+
+/// Cases whose results are reported together, a subtask if it's worth points
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Group {
+    pub name: String,
+    /// Points of subtask, scored only if all its cases pass
+    #[serde(default)]
+    pub points: Option<u32>,
+    /// Subtasks which should also pass for this subtask to score
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Limits of cases in group, used instead of those of problem
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub cases: Vec<Case>,
+}
+
+/// Group of cases worth points, like subtasks of OI problems
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Subtask {
+    pub name: String,
+    pub points: u32,
+    /// Subtasks defined before it, whose lowest score is also taken by it
+    pub depends: Vec<String>,
+}
+
+/// A case with one of `input`, `glob` and `random`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Case {
+    pub name: Option<String>,
+    /// Input of Check or Pal tests, can be `@file:` or `@glob:` of input files
+    pub input: Option<String>,
+    /// Expected output of Check tests, can be `@file:` or `@glob:` of output files
+    pub output: Option<String>,
+    /// Input of Pal tests, expanded to every combination of glob patterns
+    pub glob: Option<String>,
+    /// Input of RandomPal tests, expanded randomly for each test
+    pub random: Option<String>,
+    /// Number of RandomPal tests of `random` input, like `30%` or `x500`
+    pub share: Option<String>,
+    /// Limits of tests of case, used instead of those of group
+    pub limits: Limits,
+    /// Command-line arguments of tested(and std) program, expanded like input
+    pub args: Option<String>,
+    /// Environment variables of tested(and std) program
+    pub env: BTreeMap<String, String>,
+}
+
+impl Case {
+    /// Input with arguments and environment variables of case, written as `@args:` and `@env:`
+    /// lines before it like in test config of separators
+    fn with_invocation(&self, input: &str) -> String {
+        let args = self.args.iter().map(|args| format!("@args: {}\n", args));
+        let env = self
+            .env
+            .iter()
+            .map(|(key, value)| format!("@env: {}={}\n", key, value));
+        args.chain(env)
+            .chain(std::iter::once(with_newline(input)))
+            .collect()
+    }
+}
+
+/// An unexpanded RandomPal input, with position and label of its case and number of its tests
+type RandomInput = (usize, Label, Block, usize);
+
+impl Problem {
+    pub fn parse(input: &str, format: ProblemFormat) -> Result<Problem, ParseError> {
+        match format {
+            ProblemFormat::Toml => toml::from_str::<Problem>(input).map_err(|e| {
+                let error = ParseError::FormatError(e.message().to_string());
+                match e.span() {
+                    Some(span) => {
+                        let (line, column) = position(input, span.start);
+                        error.at(line, column)
+                    }
+                    None => error,
+                }
+            }),
+            ProblemFormat::Yaml => serde_yaml::from_str(input).map_err(|e| {
+                // Location is reported separately, so it's cut from message
+                let message = e.to_string();
+                let message = message.split(" at line ").next().unwrap_or(&message);
+                let error = ParseError::FormatError(message.to_string());
+                match e.location() {
+                    Some(location) => error.at(location.line(), location.column()),
+                    None => error,
+                }
+            }),
+        }
+        .and_then(|problem| problem.check_subtasks().map(|_| problem))
+    }
+
+    /// Groups worth points, in order
+    pub fn subtasks(&self) -> Vec<Subtask> {
+        self.groups
+            .iter()
+            .filter_map(|group| {
+                group.points.map(|points| Subtask {
+                    name: group.name.clone(),
+                    points,
+                    depends: group.depends.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Subtasks should have different names, and depend only on subtasks before them
+    fn check_subtasks(&self) -> Result<(), ParseError> {
+        let mut defined: Vec<&str> = Vec::new();
+        for group in &self.groups {
+            let error = |e: String| {
+                Err(ParseError::FormatError(format!(
+                    "group {}: {}",
+                    group.name, e
+                )))
+            };
+            if group.points.is_none() && !group.depends.is_empty() {
+                return error(String::from("depends is only used by groups with points"));
+            }
+            if group.points.is_none() {
+                continue;
+            }
+            if defined.contains(&group.name.as_str()) {
+                return error(String::from("there is another subtask of the same name"));
+            }
+            if let Some(name) = group
+                .depends
+                .iter()
+                .find(|name| !defined.contains(&name.as_str()))
+            {
+                return error(format!(
+                    "depends on {}, which is not a subtask defined before it",
+                    name
+                ));
+            }
+            defined.push(&group.name);
+        }
+        Ok(())
+    }
+
+    /// Type of tests defined, used when it's not given
+    pub fn guess_type(&self) -> PalType {
+        let cases: Vec<&Case> = self.cases().map(|(_, case)| case).collect();
+        if cases.iter().any(|case| case.random.is_some()) {
+            PalType::RandomPal
+        } else if self.generator.is_some() || cases.iter().any(|case| case.glob.is_some()) {
+            PalType::Pal
+        } else if cases.iter().any(|case| case.output.is_some()) {
+            PalType::Check
+        } else {
+            PalType::Pal
+        }
+    }
+
+    /// Cases in order, with groups they are in
+    fn cases(&self) -> impl Iterator<Item = (Option<&Group>, &Case)> {
+        self.cases.iter().map(|case| (None, case)).chain(
+            self.groups
+                .iter()
+                .flat_map(|group| group.cases.iter().map(move |case| (Some(group), case))),
+        )
+    }
+
+    /// Groups, names and limits of cases in order
+    fn case_labels(&self) -> Vec<Label> {
+        self.cases()
+            .map(|(group, case)| Label {
+                group: group.map(|group| group.name.clone()),
+                name: case.name.clone(),
+                limits: case
+                    .limits
+                    .or(group.map_or(self.limits, |group| group.limits.or(self.limits))),
+            })
+            .collect()
+    }
+
+    /// Where an input is, `position` is number of case counted from 1, the one after the last
+    /// case is generator
+    pub fn label(&self, position: usize) -> String {
+        match self.case_labels().get(position.wrapping_sub(1)) {
+            Some(Label {
+                group: Some(group),
+                name: Some(name),
+                ..
+            }) => format!("case {}({}/{})", position, group, name),
+            Some(Label { group, name, .. }) => match group.as_ref().or(name.as_ref()) {
+                Some(name) => format!("case {}({})", position, name),
+                None => format!("case {}", position),
+            },
+            None => String::from("generator"),
+        }
+    }
+
+    /// Split into inputs, for tests of type `pal_type`. Included files are relative to `base`.
+    pub(crate) fn blocks(
+        &self,
+        pal_type: &PalType,
+        base: &Path,
+    ) -> Result<Vec<ConfigBlock>, ParseError> {
+        if let PalType::RandomPal = pal_type {
+            return self
+                .random_inputs()?
+                .into_iter()
+                .map(|(position, label, block, count)| {
+                    block.check().map_err(|e| self.in_case(position, e))?;
+                    Ok(ConfigBlock::new(position, BlockJobs::Random(block, count))
+                        .with_label(label))
+                })
+                .collect();
+        }
+        if self.tests.is_some() {
+            return Err(ParseError::FormatError(format!(
+                "tests is only used by RandomPal, not {}",
+                pal_type
+            )));
+        }
+
+        let mut blocks = Vec::new();
+        let labels = self.case_labels();
+        for ((position, (_, case)), label) in (1..).zip(self.cases()).zip(labels) {
+            let error = |e: String| self.in_case(position, ParseError::FormatError(e));
+            if case.random.is_some() || case.share.is_some() {
+                return Err(error(format!(
+                    "random is only used by RandomPal, not {}",
+                    pal_type
+                )));
+            }
+            let jobs = match (pal_type, &case.input, &case.glob) {
+                (PalType::Check, Some(input), None) => {
+                    let output = case
+                        .output
+                        .as_ref()
+                        .ok_or_else(|| error(String::from("Expected output is missing")))?;
+                    BlockJobs::Fixed(
+                        check_jobs(&case.with_invocation(input), &with_newline(output), base)
+                            .map_err(error)?,
+                    )
+                }
+                (PalType::Check, _, _) => {
+                    return Err(error(String::from(
+                        "Check case should have input and output",
+                    )))
+                }
+                (PalType::Pal, _, _) if case.output.is_some() => {
+                    return Err(error(String::from(
+                        "Expected output of Pal is from std program, remove output",
+                    )))
+                }
+                (PalType::Pal, Some(input), None) => {
+                    BlockJobs::Fixed(pal_jobs(&case.with_invocation(input), base).map_err(error)?)
+                }
+                (PalType::Pal, None, Some(glob)) => {
+                    let block = case_block(&case.with_invocation(glob));
+                    block.check().map_err(|e| self.in_case(position, e))?;
+                    BlockJobs::Glob(block)
+                }
+                _ => {
+                    return Err(error(String::from(
+                        "Pal case should have one of input and glob",
+                    )))
+                }
+            };
+            blocks.push(ConfigBlock::new(position, jobs).with_label(label));
+        }
+
+        match (pal_type, &self.generator) {
+            (PalType::Pal, Some(generator)) => blocks.push(
+                ConfigBlock::new(blocks.len() + 1, BlockJobs::Generated(generator.tests))
+                    .with_label(Label {
+                        limits: self.limits,
+                        ..Label::default()
+                    }),
+            ),
+            (_, Some(_)) => {
+                return Err(ParseError::FormatError(format!(
+                    "generator is only used by Pal, not {}",
+                    pal_type
+                )))
+            }
+            (_, None) => {}
+        }
+        Ok(blocks)
+    }
+
+    /// Unexpanded RandomPal inputs, sharing `tests` tests
+    pub(crate) fn random_inputs(&self) -> Result<Vec<RandomInput>, ParseError> {
+        let tests_num = self.tests.ok_or_else(|| {
+            ParseError::FormatError(String::from("tests is missing, it's needed by RandomPal"))
+        })?;
+        if self.generator.is_some() {
+            return Err(ParseError::FormatError(String::from(
+                "generator is only used by Pal, not RandomPal",
+            )));
+        }
+
+        let mut inputs = Vec::new();
+        let mut shares = Vec::new();
+        let labels = self.case_labels();
+        for ((position, (_, case)), label) in (1..).zip(self.cases()).zip(labels) {
+            let error = |e: String| self.in_case(position, ParseError::FormatError(e));
+            let random = match (&case.random, &case.input, &case.output, &case.glob) {
+                (Some(random), None, None, None) => random,
+                _ => {
+                    return Err(error(String::from(
+                        "RandomPal case should have random only",
+                    )))
+                }
+            };
+            shares.push(Share::parse(case.share.as_deref().unwrap_or("")).map_err(error)?);
+            inputs.push((position, label, case_block(&case.with_invocation(random))));
+        }
+        if inputs.is_empty() {
+            return Err(ParseError::FormatError(String::from(
+                "No random input defined in test config",
+            )));
+        }
+
+        let counts = allocate(tests_num, &shares).map_err(ParseError::FormatError)?;
+        Ok(inputs
+            .into_iter()
+            .zip(counts)
+            .map(|((position, label, block), count)| (position, label, block, count))
+            .collect())
+    }
+
+    fn in_case(&self, position: usize, error: ParseError) -> ParseError {
+        ParseError::InCase {
+            case: self.label(position),
+            error: Box::new(error),
+        }
+    }
+}
+
+/// Input of a case, positions in it are counted from its first line
+fn case_block(input: &str) -> Block {
+    Block {
+        line: 1,
+        input: with_newline(input),
+        annotation: String::new(),
+    }
+}
+
+/// Text ended with a newline like lines in test config of separators, as strings in TOML or
+/// YAML often don't have one
+fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+/// Line and column of byte `offset` in `input`, counted from 1
+fn position(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset.min(input.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::Job;
+    use crate::parser::{GlobLimit, TestConfig};
+
+    fn jobs(pal_type: &PalType, path: &str, input: &str) -> Result<Vec<Job>, ParseError> {
+        let (_, jobs) =
+            TestConfig::file(path).parse_lazy(pal_type, input, 0, GlobLimit::UNLIMITED)?;
+        jobs.collect()
+    }
+
+    #[test]
+    fn test_parse_problem() {
+        let path = "tests/problem/check.toml";
+        let config = std::fs::read_to_string(path).unwrap();
+        let problem = Problem::parse(&config, ProblemFormat::Toml).unwrap();
+        assert_eq!(problem.comparator, Comparator::Tokens);
+        assert_eq!(problem.limits.time, Some(2000));
+        assert!(matches!(problem.guess_type(), PalType::Check));
+        assert_eq!(problem.checker.unwrap().source, "checker.c");
+
+        let jobs = jobs(&PalType::Check, path, &config).unwrap();
+        let names: Vec<String> = jobs.iter().map(|job| job.label()).collect();
+        assert_eq!(
+            names,
+            [
+                "0(sample)",
+                "1(files/../parser/include/cases/1.in)",
+                "2(files/../parser/include/cases/2.in)",
+                "3(files/../parser/include/cases/10.in)",
+                "4(files/../parser/include/cases/single.in)",
+                "5(files/single/../parser/include/cases/single.in)",
+            ]
+        );
+        assert_eq!(jobs[0].input, b"1 2\n");
+        assert_eq!(jobs[0].expected_output, b"3\n");
+        assert_eq!(jobs[5].input, b"7 8\n");
+        let limits: Vec<(Option<u64>, Option<u64>)> = jobs
+            .iter()
+            .map(|job| (job.time_limit, job.memory_limit))
+            .collect();
+        assert_eq!(limits[0], (Some(2000), None));
+        assert_eq!(limits[1], (Some(500), Some(64 << 20)));
+        assert_eq!(limits[5], (Some(1500), Some(64 << 20)));
+
+        assert_eq!(
+            TestConfig::file(path)
+                .count_blocks(&PalType::Check, &config, u128::MAX)
+                .unwrap(),
+            [
+                (String::from("case 1(sample)"), 1),
+                (String::from("case 2(files)"), 4),
+                (String::from("case 3(files/single)"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_problem_random() {
+        let path = "tests/problem/random.yaml";
+        let config = std::fs::read_to_string(path).unwrap();
+        let jobs = jobs(&PalType::RandomPal, path, &config).unwrap();
+        assert_eq!(jobs.len(), 10);
+        assert!(jobs[..3]
+            .iter()
+            .all(|job| job.input.len() == 2 && job.name.as_deref() == Some("small")));
+        assert!(jobs[3..]
+            .iter()
+            .all(|job| job.input.len() == 6 && job.name.is_none()));
+
+        assert_eq!(jobs[0].time_limit, Some(100));
+        assert_eq!(jobs[5].time_limit, None);
+        assert_eq!(jobs[0].args[0], "--mode");
+        assert!(["a", "b", "c"].contains(&jobs[0].args[1].as_str()));
+        assert_eq!(jobs[0].env, [(String::from("SIZE"), String::from("small"))]);
+        assert!(jobs[5].args.is_empty() && jobs[5].env.is_empty());
+
+        let blocks = TestConfig::file(path).random_pal_inputs(&config).unwrap();
+        assert_eq!(
+            crate::parser::random_pal_job(&blocks, 0, 5).unwrap().input,
+            jobs[5].input
+        );
+        assert_eq!(
+            crate::parser::random_pal_job(&blocks, 0, 1).unwrap(),
+            jobs[1]
+        );
+    }
+
+    #[test]
+    fn test_parse_subtasks() {
+        let path = "tests/problem/subtasks.toml";
+        let config = std::fs::read_to_string(path).unwrap();
+        assert_eq!(
+            TestConfig::file(path).subtasks(&config).unwrap(),
+            [
+                Subtask {
+                    name: String::from("small"),
+                    points: 30,
+                    depends: Vec::new(),
+                },
+                Subtask {
+                    name: String::from("large"),
+                    points: 70,
+                    depends: vec![String::from("small")],
+                },
+            ]
+        );
+
+        let error = |config: &str| {
+            format!(
+                "{:?}",
+                Problem::parse(config, ProblemFormat::Toml).unwrap_err()
+            )
+        };
+        assert_eq!(
+            error("[[groups]]\nname = \"a\"\npoints = 10\ndepends = [\"b\"]\n"),
+            "Format error: group a: depends on b, which is not a subtask defined before it"
+        );
+        assert!(error("[[groups]]\nname = \"a\"\ndepends = [\"b\"]\n").contains("only used"));
+        assert!(error(
+            "[[groups]]\nname = \"a\"\npoints = 10\n[[groups]]\nname = \"a\"\npoints = 20\n"
+        )
+        .contains("same name"));
+    }
+
+    #[test]
+    fn test_parse_problem_error() {
+        let error = |pal_type: &PalType, config: &str| {
+            format!("{:?}", jobs(pal_type, "problem.toml", config).unwrap_err())
+        };
+        assert_eq!(
+            error(&PalType::Pal, "[[cases]]\ninput = 1\n"),
+            "problem.toml:2:9: Format error: invalid type: integer `1`, expected a string"
+        );
+        assert!(error(&PalType::Pal, "[limit]\ntime = 1\n")
+            .starts_with("problem.toml:1:2: Format error: unknown field `limit`"));
+        assert_eq!(
+            error(&PalType::Check, "[[cases]]\nname = \"a\"\ninput = \"1\"\n"),
+            "case 1(a): Format error: Expected output is missing"
+        );
+        assert_eq!(
+            error(&PalType::Pal, "[[cases]]\nglob = \"a [5-1]\"\n"),
+            "case 1: line 1, column 3: Format error: Invalid range: [5-1]"
+        );
+        assert!(
+            error(&PalType::RandomPal, "[[cases]]\nrandom = \"[1-9]\"\n")
+                .contains("tests is missing")
+        );
+
+        let yaml = jobs(&PalType::Pal, "problem.yml", "cases:\n  - input: [1]\n").unwrap_err();
+        assert!(matches!(
+            yaml,
+            ParseError::Located {
+                line: 2,
+                column: 12,
+                ..
+            }
+        ));
+    }
+}
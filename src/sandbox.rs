@@ -0,0 +1,335 @@
+//! Sandbox of tested programs on Linux, for running untrusted submissions. It's built on
+//! namespaces, seccomp and rlimits: the program has no network, file system is read-only except
+//! working directory of job, number of processes is limited, and the program is killed when it
+//! makes a forbidden system call.
+use std::path::Path;
+use std::process::Command;
+
+/// Most processes(and threads) a sandboxed program can have
+pub const MAX_PROCESSES: u64 = 64;
+
+/// Run `command` in sandbox, where `dir` is the only writable directory
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn sandbox(command: &mut Command, dir: &Path) -> Result<(), String> {
+    linux::sandbox(command, dir)
+}
+
+/// Sandbox is built on features of Linux
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn sandbox(_command: &mut Command, _dir: &Path) -> Result<(), String> {
+    Err(String::from(
+        "Sandbox is only supported on Linux(x86_64 and aarch64)",
+    ))
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use super::MAX_PROCESSES;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Set up sandbox in child process before exec
+    pub(super) fn sandbox(command: &mut Command, dir: &Path) -> Result<(), String> {
+        use std::ffi::{CStr, CString};
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::process::CommandExt;
+
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| format!("Invalid working directory: {}", dir.display()))?;
+        // Sandboxed program is nobody in its user namespace, so it loses capabilities on exec
+        // Safety: getuid and getgid always succeed
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("65534 {} 1", uid);
+        let gid_map = format!("65534 {} 1", gid);
+        let filter = seccomp_filter();
+        let path = |bytes: &'static [u8]| CStr::from_bytes_with_nul(bytes).unwrap();
+        let (setgroups, uid_map_path, gid_map_path, root) = (
+            path(b"/proc/self/setgroups\0"),
+            path(b"/proc/self/uid_map\0"),
+            path(b"/proc/self/gid_map\0"),
+            path(b"/\0"),
+        );
+
+        // Safety: only system calls are made between fork and exec, everything allocated is
+        // prepared before
+        unsafe {
+            command.pre_exec(move || {
+                check(libc::unshare(
+                    libc::CLONE_NEWUSER
+                        | libc::CLONE_NEWNS
+                        | libc::CLONE_NEWNET
+                        | libc::CLONE_NEWIPC
+                        | libc::CLONE_NEWUTS,
+                ))?;
+                write_file(setgroups, b"deny")?;
+                write_file(uid_map_path, uid_map.as_bytes())?;
+                write_file(gid_map_path, gid_map.as_bytes())?;
+
+                // Mounts are changed only in new mount namespace, then everything but working
+                // directory is made read-only
+                let none = std::ptr::null();
+                check(libc::mount(
+                    none,
+                    root.as_ptr(),
+                    none,
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    std::ptr::null(),
+                ))?;
+                check(libc::mount(
+                    dir.as_ptr(),
+                    dir.as_ptr(),
+                    none,
+                    libc::MS_BIND | libc::MS_REC,
+                    std::ptr::null(),
+                ))?;
+                set_read_only(root, true)?;
+                set_read_only(&dir, false)?;
+                // Current directory was set before, on the mount under working directory
+                check(libc::chdir(dir.as_ptr()))?;
+
+                set_limit(libc::RLIMIT_NPROC as _, MAX_PROCESSES)?;
+                set_limit(libc::RLIMIT_CORE as _, 0)?;
+                check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+                let program = libc::sock_fprog {
+                    len: filter.len() as u16,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ))
+            });
+        }
+        Ok(())
+    }
+
+    /// System calls which kill sandboxed program. Besides changing the sandbox, they are for
+    /// administrating system or inspecting other processes.
+    const FORBIDDEN_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_pidfd_getfd,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_mount_setattr,
+        libc::SYS_open_tree,
+        libc::SYS_move_mount,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_acct,
+        libc::SYS_settimeofday,
+        libc::SYS_clock_settime,
+        libc::SYS_sethostname,
+        libc::SYS_setdomainname,
+    ];
+
+    /// Address families of network sockets, creating them kills sandboxed program. Other sockets
+    /// like `AF_UNIX` are allowed, as runtimes and libraries use them locally.
+    const FORBIDDEN_FAMILIES: &[libc::c_int] = &[libc::AF_INET, libc::AF_INET6, libc::AF_PACKET];
+
+    /// `AUDIT_ARCH_*` of system calls allowed, those of other architectures are forbidden
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// BPF program of seccomp, which kills the process making a forbidden system call
+    fn seccomp_filter() -> Vec<libc::sock_filter> {
+        let statement = |code: u32, k: u32| libc::sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        };
+        let jump = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        };
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let kill = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS);
+        let allow = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW);
+        // Offsets of `arch` and `nr` in `seccomp_data`
+        let mut filter = vec![
+            statement(load, 4),
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                AUDIT_ARCH,
+                1,
+                0,
+            ),
+            kill,
+            statement(load, 0),
+        ];
+        // System calls of x32 ABI are numbered from 0x40000000
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([
+            jump(
+                libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+                0x4000_0000,
+                0,
+                1,
+            ),
+            kill,
+        ]);
+        // `socket` is checked by its family, the first argument. It's an int, the lower half of
+        // `args[0]` at offset 16 on little endian architectures.
+        let families = FORBIDDEN_FAMILIES.len() as u8;
+        filter.extend([
+            jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                libc::SYS_socket as u32,
+                0,
+                families + 3,
+            ),
+            statement(load, 16),
+        ]);
+        for (i, family) in FORBIDDEN_FAMILIES.iter().enumerate() {
+            filter.push(jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                *family as u32,
+                families - i as u8,
+                0,
+            ));
+        }
+        filter.extend([allow, kill]);
+        for syscall in FORBIDDEN_SYSCALLS {
+            filter.extend([
+                jump(
+                    libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                    *syscall as u32,
+                    0,
+                    1,
+                ),
+                kill,
+            ]);
+        }
+        filter.push(allow);
+        filter
+    }
+
+    fn check(result: libc::c_int) -> std::io::Result<()> {
+        match result {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_file(path: &std::ffi::CStr, content: &[u8]) -> std::io::Result<()> {
+        // Safety: path is a C string, and content is valid for its length
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            check(fd)?;
+            let written = libc::write(fd, content.as_ptr().cast(), content.len());
+            libc::close(fd);
+            match written {
+                -1 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// Make mounts under `path` read-only or writable
+    fn set_read_only(path: &std::ffi::CStr, read_only: bool) -> std::io::Result<()> {
+        /// `struct mount_attr` of `mount_setattr`
+        #[repr(C)]
+        struct MountAttr {
+            attr_set: u64,
+            attr_clr: u64,
+            propagation: u64,
+            userns_fd: u64,
+        }
+        const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+        let (attr_set, attr_clr) = match read_only {
+            true => (MOUNT_ATTR_RDONLY, 0),
+            false => (0, MOUNT_ATTR_RDONLY),
+        };
+        let attr = MountAttr {
+            attr_set,
+            attr_clr,
+            propagation: 0,
+            userns_fd: 0,
+        };
+        // Safety: path is a C string, and attr is a `struct mount_attr` of the size passed
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                libc::AT_RECURSIVE,
+                &attr as *const MountAttr,
+                std::mem::size_of::<MountAttr>(),
+            )
+        };
+        check(result as libc::c_int)
+    }
+
+    /// Set both soft and hard limit of `resource`
+    fn set_limit(resource: libc::c_int, limit: u64) -> std::io::Result<()> {
+        let limit = libc::rlimit {
+            rlim_cur: limit as libc::rlim_t,
+            rlim_max: limit as libc::rlim_t,
+        };
+        // Safety: limit is a valid `rlimit`
+        check(unsafe { libc::setrlimit(resource as _, &limit) })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_seccomp_filter() {
+            let filter = seccomp_filter();
+            let kills = filter
+                .iter()
+                .filter(|statement| statement.k == libc::SECCOMP_RET_KILL_PROCESS)
+                .count();
+            let x32 = cfg!(target_arch = "x86_64") as usize;
+            assert_eq!(kills, 1 + x32 + 1 + FORBIDDEN_SYSCALLS.len());
+            assert!(!FORBIDDEN_SYSCALLS.contains(&libc::SYS_socket));
+            assert_eq!(filter.last().unwrap().k, libc::SECCOMP_RET_ALLOW);
+            // Every jump lands on a statement of the program
+            assert!(filter
+                .iter()
+                .enumerate()
+                .filter(|(_, statement)| statement.code as u32 & 0x07 == libc::BPF_JMP)
+                .all(|(i, statement)| {
+                    i + 1 + (statement.jt.max(statement.jf) as usize) < filter.len()
+                }));
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::datadir::DataLayout;
+use crate::limits::StdLimit;
 use crate::pal::PalType;
+use crate::parser::{GlobLimit, TestConfig};
 use crate::{CLIError, Cli, Commands};
+use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::{Path, PathBuf};
@@ -11,16 +15,56 @@ pub struct Session {
     pub current_working_directory: String,
     pub compiler: String,
     pub compiler_arguments: String,
-    pub timeout: u64,
+    /// Time limit of tested program in milliseconds given by `--timeout`, which takes precedence
+    /// over the one of test config
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub std_limit: StdLimit,
+    /// Run tested program in sandbox
+    #[serde(default)]
+    pub sandbox: bool,
     pub source: String,
     pub std_source: String,
+    /// Test config file, or test data directory if `data_layout` is set
     pub test_config_filename: String,
+    #[serde(default)]
+    pub data_layout: Option<DataLayout>,
     pub test_info_directory: String,
     pub job_store_filepath: String,
+    /// Master seed for generating random inputs
+    #[serde(default)]
+    pub seed: u64,
+    /// Limit of number of tests expanded from glob patterns
+    #[serde(default)]
+    pub glob_limit: GlobLimit,
     pub run: bool,
 }
 
+/// Time limit of tested program if neither `--timeout` nor test config gives one
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
 impl Session {
+    /// Parse session file, where those written before time limit was in milliseconds have
+    /// `timeout` in seconds
+    pub fn parse(json_content: &str) -> Result<Session, serde_json::Error> {
+        let mut session: serde_json::Value = serde_json::from_str(json_content)?;
+        if let Some(fields) = session.as_object_mut() {
+            if let Some(timeout) = fields.remove("timeout") {
+                let timeout_ms = timeout.as_u64().map(|timeout| timeout * 1000);
+                fields.insert(String::from("timeout_ms"), timeout_ms.into());
+            }
+        }
+        serde_json::from_value(session)
+    }
+
+    pub fn test_config(&self) -> TestConfig {
+        TestConfig {
+            path: self.test_config_filename.clone(),
+            data_layout: self.data_layout,
+        }
+    }
+
     pub fn build(cli: Cli, data_directory: PathBuf) -> Result<Session, CLIError> {
         let current_working_directory = env::current_dir().map_err(|e| {
             CLIError::EnvironmentError(format!("Cannot get current working directory: {:?}", e))
@@ -30,15 +74,30 @@ impl Session {
         let compiler_arguments = cli
             .compiler_args
             .unwrap_or(String::from("-Wall -Wextra -lm"));
+        let std_limit = StdLimit::from_args(cli.std_timeout, cli.std_multiplier);
+        let glob_limit = GlobLimit {
+            max_combinations: cli
+                .max_combinations
+                .unwrap_or(GlobLimit::default().max_combinations),
+            sample: cli.sample,
+        };
 
         match &cli.command {
             Commands::Check {
                 source,
                 test_config,
+                data_dir,
+                layout,
             } => {
                 let source_path = current_working_directory.join(source);
-                let test_config_path =
-                    build_test_config_path(&current_working_directory, &source_path, test_config)?;
+                let test_config_path = match data_dir {
+                    Some(data_dir) => current_working_directory.join(data_dir),
+                    None => build_test_config_path(
+                        &current_working_directory,
+                        &source_path,
+                        test_config,
+                    )?,
+                };
                 let job_store_filepath = build_job_store_filename(&source_path, data_directory)?
                     .to_str()
                     .unwrap()
@@ -68,12 +127,17 @@ impl Session {
                         .to_string(),
                     compiler,
                     compiler_arguments,
-                    timeout: cli.timeout.unwrap_or(10),
+                    timeout_ms: cli.timeout,
+                    std_limit,
+                    sandbox: cli.sandbox,
                     source: source_path.to_str().unwrap().to_string(),
                     std_source: String::new(),
                     test_config_filename: test_config_path.to_str().unwrap().to_string(),
+                    data_layout: data_dir.as_ref().map(|_| layout.unwrap_or_default()),
                     test_info_directory: test_info_directory.to_str().unwrap().to_string(),
                     job_store_filepath,
+                    seed: thread_rng().gen(),
+                    glob_limit,
                     run: false,
                 })
             }
@@ -114,7 +178,9 @@ impl Session {
                         .to_string(),
                     compiler,
                     compiler_arguments,
-                    timeout: cli.timeout.unwrap_or(10),
+                    timeout_ms: cli.timeout,
+                    std_limit,
+                    sandbox: cli.sandbox,
                     source: source_path.to_str().unwrap().to_string(),
                     std_source: build_std_source_path(
                         &current_working_directory,
@@ -125,8 +191,11 @@ impl Session {
                     .unwrap()
                     .to_string(),
                     test_config_filename: test_config_path.to_str().unwrap().to_string(),
+                    data_layout: None,
                     test_info_directory: test_info_directory.to_str().unwrap().to_string(),
                     job_store_filepath,
+                    seed: thread_rng().gen(),
+                    glob_limit,
                     run: false,
                 })
             }
@@ -134,6 +203,15 @@ impl Session {
                 source,
                 std_source,
                 test_config,
+                seed,
+                ..
+            }
+            | Commands::Stress {
+                source,
+                std_source,
+                test_config,
+                seed,
+                ..
             } => {
                 let source_path = current_working_directory.join(source);
                 let test_config_path =
@@ -167,7 +245,9 @@ impl Session {
                         .to_string(),
                     compiler,
                     compiler_arguments,
-                    timeout: cli.timeout.unwrap_or(10),
+                    timeout_ms: cli.timeout,
+                    std_limit,
+                    sandbox: cli.sandbox,
                     source: source_path.to_str().unwrap().to_string(),
                     std_source: build_std_source_path(
                         &current_working_directory,
@@ -178,21 +258,24 @@ impl Session {
                     .unwrap()
                     .to_string(),
                     test_config_filename: test_config_path.to_str().unwrap().to_string(),
+                    data_layout: None,
                     test_info_directory: test_info_directory.to_str().unwrap().to_string(),
                     job_store_filepath,
+                    seed: seed.unwrap_or_else(|| thread_rng().gen()),
+                    glob_limit,
                     run: false,
                 })
             }
-            _ => Err(CLIError::OtherError(format!(
-                "Calling Session::build() with invalid cli subcommand"
-            ))),
+            _ => Err(CLIError::OtherError(
+                "Calling Session::build() with invalid cli subcommand".to_string(),
+            )),
         }
     }
 }
 
 fn build_test_config_path(
-    cwd: &PathBuf,
-    source_path: &PathBuf,
+    cwd: &Path,
+    source_path: &Path,
     test_config_optional: &Option<String>,
 ) -> Result<PathBuf, CLIError> {
     let source_prefix = source_path
@@ -210,7 +293,7 @@ fn build_test_config_path(
         let root = Path::new("/");
         Ok(source_path
             .parent()
-            .unwrap_or_else(|| root)
+            .unwrap_or(root)
             .join(format!("{}.test", source_prefix)))
     } else {
         Ok(cwd.join(test_config_optional.as_ref().unwrap()))
@@ -218,7 +301,7 @@ fn build_test_config_path(
 }
 
 fn build_job_store_filename(
-    source_path: &PathBuf,
+    source_path: &Path,
     data_directory: PathBuf,
 ) -> Result<PathBuf, CLIError> {
     let source_prefix = source_path
@@ -236,8 +319,8 @@ fn build_job_store_filename(
 }
 
 fn build_std_source_path(
-    cwd: &PathBuf,
-    source_path: &PathBuf,
+    cwd: &Path,
+    source_path: &Path,
     std_source_optional: &Option<String>,
 ) -> Result<PathBuf, CLIError> {
     let source_prefix = source_path
@@ -270,3 +353,25 @@ fn build_std_source_path(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_old_session() {
+        let fields = r#""uuid": "ebea342f-2f24-4441-93f4-ccba748063b9", "pal_type": "Check",
+            "current_working_directory": "/tmp", "compiler": "gcc", "compiler_arguments": "",
+            "source": "/tmp/a.c", "std_source": "", "test_config_filename": "/tmp/a.test",
+            "test_info_directory": "/tmp/tests_info/a", "job_store_filepath": "/tmp/a.json",
+            "run": true"#;
+        // Written when time limit was in seconds
+        let session = Session::parse(&format!("{{{}, \"timeout\": 2}}", fields)).unwrap();
+        assert_eq!(session.timeout_ms, Some(2000));
+
+        let session = Session::parse(&format!("{{{}, \"timeout_ms\": 1500}}", fields)).unwrap();
+        assert_eq!(session.timeout_ms, Some(1500));
+        let session = Session::parse(&format!("{{{}}}", fields)).unwrap();
+        assert_eq!(session.timeout_ms, None);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::job::Job;
+use crate::pal::{run_retest, PalStore, PalType, RetestBase};
+use crate::parser::ParseError;
+use crate::problem::Subtask;
+use crate::session::Session;
+use crate::{build_retest_config, load_store, session_settings, CLIError};
+use std::collections::HashSet;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+pub fn run_watch(session: Session, interval: Duration) -> Result<(), CLIError> {
+    let mut watched = watched_files(&session);
+
+    println!("Watching for modification of:");
+    watched.iter().for_each(|path| println!("  {}", path));
+    println!("Press Ctrl-C to stop.");
+
+    let mut last_modified = modified_times(&watched);
+
+    loop {
+        thread::sleep(interval);
+        let modified = modified_times(&watched);
+        if modified == last_modified {
+            continue;
+        }
+        let config_changed = changed_files(&watched, &last_modified, &modified)
+            .contains(&&session.test_config_filename);
+        last_modified = modified;
+        if config_changed {
+            // Test config may name another generator or checker
+            watched = watched_files(&session);
+            last_modified = modified_times(&watched);
+        }
+
+        println!();
+        println!("Modification detected, rerunning tests...");
+        if let Err(e) = rerun(&session, config_changed) {
+            println!("{:?}", e);
+        }
+        println!("Watching for modification...");
+    }
+}
+
+/// Sources of programs and test config of session, including generator and checker named in
+/// test config
+fn watched_files(session: &Session) -> Vec<String> {
+    let mut watched = vec![session.source.clone()];
+    if let PalType::Pal | PalType::RandomPal = session.pal_type {
+        watched.push(session.std_source.clone());
+    }
+    watched.push(session.test_config_filename.clone());
+    if let Ok(settings) = session_settings(session) {
+        watched.extend(settings.generator_source);
+        watched.extend(settings.checker_source);
+    }
+    watched
+}
+
+fn modified_times(paths: &[String]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn changed_files<'a>(
+    paths: &'a [String],
+    last_modified: &[Option<SystemTime>],
+    modified: &[Option<SystemTime>],
+) -> Vec<&'a String> {
+    paths
+        .iter()
+        .zip(last_modified.iter().zip(modified))
+        .filter(|(_, (last, current))| last != current)
+        .map(|(path, _)| path)
+        .collect()
+}
+
+fn rerun(session: &Session, config_changed: bool) -> Result<(), CLIError> {
+    let previous = load_store(&session.job_store_filepath).ok();
+
+    // Reuse stored jobs unless test config changed, so random inputs are kept between runs
+    let (mut job_list, subtasks, seed): (Vec<Job>, Vec<Subtask>, Option<u64>) =
+        match (&previous, config_changed) {
+            (Some(store), false) => (
+                store
+                    .job_failed
+                    .iter()
+                    .chain(store.job_passed.iter())
+                    .map(|(job, _, _)| job.clone())
+                    .collect(),
+                store.subtask_list(),
+                store.seed,
+            ),
+            _ => {
+                let test_config = session.test_config();
+                let test_config_str = test_config.read().map_err(CLIError::IOError)?;
+                test_config
+                    .parse_lazy(
+                        &session.pal_type,
+                        &test_config_str,
+                        session.seed,
+                        session.glob_limit,
+                    )
+                    .and_then(|(_, jobs)| jobs.collect::<Result<Vec<Job>, ParseError>>())
+                    .and_then(|jobs| {
+                        let subtasks = test_config.subtasks(&test_config_str)?;
+                        Ok((jobs, subtasks, Some(session.seed)))
+                    })
+                    .map_err(|e| CLIError::ParseError(format!("{:?}", test_config.locate(e))))?
+            }
+        };
+
+    // Run previously failed tests first
+    let failed_ids: HashSet<usize> = previous
+        .as_ref()
+        .map(|store| store.job_failed.iter().map(|(job, _, _)| job.id).collect())
+        .unwrap_or_default();
+    job_list.sort_by_key(|job| (!failed_ids.contains(&job.id), job.id));
+
+    let regenerate = matches!(session.pal_type, PalType::Pal | PalType::RandomPal);
+    let (pal_type, mut compile_config, timeout) = build_retest_config(session, regenerate)?;
+    // Stored jobs of generator keep their seeds, inputs are generated again from them
+    compile_config.generator_source = session_settings(session)?.generator_source;
+
+    run_retest(
+        pal_type,
+        compile_config,
+        job_list,
+        RetestBase {
+            // Every stored job is retested
+            job_passed: Vec::new(),
+            subtasks,
+            seed,
+        },
+        &session.job_store_filepath,
+        timeout,
+    )
+    .map_err(|e| CLIError::PalError(format!("Error while running tests: {:?}", e)))?;
+
+    if let Some(previous) = previous {
+        let current = load_store(&session.job_store_filepath)?;
+        print_delta(&previous, &current);
+    }
+
+    Ok(())
+}
+
+fn print_delta(previous: &PalStore, current: &PalStore) {
+    let ids = |jobs: &Vec<(Job, _, bool)>| -> HashSet<usize> {
+        jobs.iter().map(|(job, _, _)| job.id).collect()
+    };
+    let previous_passed = ids(&previous.job_passed);
+    let previous_failed = ids(&previous.job_failed);
+
+    let mut fixed: Vec<usize> = current
+        .job_passed
+        .iter()
+        .map(|(job, _, _)| job.id)
+        .filter(|id| previous_failed.contains(id))
+        .collect();
+    let mut broken: Vec<usize> = current
+        .job_failed
+        .iter()
+        .map(|(job, _, _)| job.id)
+        .filter(|id| previous_passed.contains(id))
+        .collect();
+    let still_failing = current
+        .job_failed
+        .iter()
+        .filter(|(job, _, _)| previous_failed.contains(&job.id))
+        .count();
+    fixed.sort();
+    broken.sort();
+
+    println!(
+        "Delta: fixed = {}{}, broken = {}{}, still failing = {}",
+        fixed.len(),
+        format_ids(&fixed),
+        broken.len(),
+        format_ids(&broken),
+        still_failing
+    );
+}
+
+fn format_ids(ids: &[usize]) -> String {
+    const MAX_SHOWN: usize = 10;
+    if ids.is_empty() {
+        return String::new();
+    }
+    let shown: Vec<String> = ids
+        .iter()
+        .take(MAX_SHOWN)
+        .map(|id| id.to_string())
+        .collect();
+    if ids.len() > MAX_SHOWN {
+        format!(" (id: {}, ...)", shown.join(", "))
+    } else {
+        format!(" (id: {})", shown.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::Parser;
+    use std::env;
+    use std::fs::File;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_watched_files() {
+        let cli = Cli::parse_from([
+            "rpal",
+            "pal",
+            "tests/watch/add.c",
+            "tests/watch/add_std.c",
+            "tests/watch/watch.toml",
+        ]);
+        let session = Session::build(cli, env::temp_dir()).unwrap();
+        let watched = watched_files(&session);
+        let names: Vec<&str> = watched
+            .iter()
+            .map(|path| path.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["add.c", "add_std.c", "watch.toml", "gen.c", "checker.c"]
+        );
+    }
+
+    #[test]
+    fn test_changed_files() {
+        let dir = env::temp_dir().join(format!("rpal-watch-{}", Uuid::new_v4()));
+        fs::create_dir(&dir).unwrap();
+        let paths: Vec<String> = ["a.c", "b.c", "c.c"]
+            .iter()
+            .map(|name| dir.join(name).to_str().unwrap().to_string())
+            .collect();
+        for path in &paths[..2] {
+            fs::write(path, "").unwrap();
+        }
+        let last_modified = modified_times(&paths);
+        assert_eq!(last_modified, modified_times(&paths));
+
+        // Modified, created and removed files are all changes
+        let later = last_modified[0].unwrap() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&paths[0])
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(
+            changed_files(&paths, &last_modified, &modified_times(&paths)),
+            [&paths[0]]
+        );
+        fs::write(&paths[2], "").unwrap();
+        fs::remove_file(&paths[1]).unwrap();
+        assert_eq!(
+            changed_files(&paths, &last_modified, &modified_times(&paths)),
+            [&paths[0], &paths[1], &paths[2]]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
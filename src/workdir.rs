@@ -0,0 +1,103 @@
+//! Working directories of jobs. Each job runs in a fresh temporary directory, so programs
+//! creating scratch files don't race each other or litter the source tree.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Temporary working directory of a job, removed when dropped unless it's kept
+pub struct JobDir {
+    path: PathBuf,
+}
+
+impl JobDir {
+    /// Create a directory with copies of `fixtures`, which can be files or directories
+    pub fn create(fixtures: &[String]) -> Result<JobDir, String> {
+        let dir = JobDir {
+            path: env::temp_dir().join(format!("rpal-job-{}", Uuid::new_v4())),
+        };
+        fs::create_dir(&dir.path).map_err(|e| {
+            format!(
+                "Cannot create working directory {}: {}",
+                dir.path.display(),
+                e
+            )
+        })?;
+        for fixture in fixtures {
+            let fixture = Path::new(fixture);
+            let name = fixture
+                .file_name()
+                .ok_or_else(|| format!("Invalid fixture: {}", fixture.display()))?;
+            copy_all(fixture, &dir.path.join(name))
+                .map_err(|e| format!("Cannot copy fixture {}: {}", fixture.display(), e))?;
+        }
+        Ok(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move directory to `to` for inspection, replacing what's there
+    pub fn keep(self, to: &Path) -> Result<(), String> {
+        let _ = fs::remove_dir_all(to);
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+        }
+        // Temporary directory may be on another file system, where it can only be copied
+        fs::rename(&self.path, to)
+            .or_else(|_| copy_all(&self.path, to))
+            .map_err(|e| format!("Cannot keep working directory in {}: {}", to.display(), e))
+    }
+}
+
+impl Drop for JobDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Copy file, or directory with everything in it
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_all(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_dir() {
+        let fixtures = [
+            String::from("tests/parser/include/cases/1.in"),
+            String::from("tests/parser/include/cases"),
+        ];
+        let dir = JobDir::create(&fixtures).unwrap();
+        let path = dir.path().to_path_buf();
+        assert_eq!(fs::read(path.join("1.in")).unwrap(), b"1 1\n");
+        assert!(path.join("cases").join("single.out").is_file());
+        drop(dir);
+        assert!(!path.exists());
+
+        let dir = JobDir::create(&[]).unwrap();
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("scratch"), "x").unwrap();
+        let kept = env::temp_dir().join(format!("rpal-kept-{}", Uuid::new_v4()));
+        dir.keep(&kept.join("work")).unwrap();
+        assert!(!path.exists());
+        assert_eq!(fs::read(kept.join("work").join("scratch")).unwrap(), b"x");
+        fs::remove_dir_all(&kept).unwrap();
+
+        assert!(JobDir::create(&[String::from("tests/missing")]).is_err());
+    }
+}
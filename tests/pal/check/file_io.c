@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+int main(void) {
+  int x = 0;
+  freopen("add.in", "r", stdin);
+  freopen("add.out", "w", stdout);
+  scanf("%d", &x);
+  printf("%d", x + 1);
+}
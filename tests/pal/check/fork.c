@@ -0,0 +1,15 @@
+#include <stdio.h>
+#include <unistd.h>
+
+int main(void) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    for (;;) {
+    }
+  }
+  FILE *file = fopen("pid", "w");
+  fprintf(file, "%d", pid);
+  fclose(file);
+  for (;;) {
+  }
+}
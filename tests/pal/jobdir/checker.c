@@ -0,0 +1,16 @@
+#include <stdio.h>
+
+// Output is accepted if it's input, and base.txt is found in working directory
+int main(int argc, char *argv[]) {
+  FILE *base = fopen("base.txt", "r");
+  FILE *input = fopen(argv[1], "r");
+  FILE *output = fopen(argv[2], "r");
+  int a = 0, b = 1;
+  if (argc < 5 || base == NULL || input == NULL || output == NULL) {
+    return 3;
+  }
+  if (fscanf(input, "%d", &a) != 1 || fscanf(output, "%d", &b) != 1 || a != b) {
+    return 1;
+  }
+  return 0;
+}
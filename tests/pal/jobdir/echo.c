@@ -0,0 +1,7 @@
+#include <stdio.h>
+
+int main(void) {
+  int a = 0;
+  scanf("%d", &a);
+  printf("%d\n", a);
+}
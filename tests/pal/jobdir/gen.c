@@ -0,0 +1,12 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+// Input is seed plus the number in base.txt, a fixture of working directory
+int main(int argc, char *argv[]) {
+  FILE *base = fopen("base.txt", "r");
+  int b = 0;
+  if (argc < 2 || base == NULL || fscanf(base, "%d", &b) != 1) {
+    return 1;
+  }
+  printf("%d\n", atoi(argv[1]) + b);
+}
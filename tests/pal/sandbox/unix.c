@@ -0,0 +1,6 @@
+#include <stdio.h>
+#include <sys/socket.h>
+
+int main(void) {
+  printf("%d\n", socket(AF_UNIX, SOCK_STREAM, 0) >= 0);
+}
@@ -0,0 +1,9 @@
+#include <errno.h>
+#include <stdio.h>
+
+// Only working directory is writable in sandbox
+int main(void) {
+  FILE *outside = fopen("/tmp/rpal-sandbox-outside", "w");
+  FILE *inside = fopen("scratch", "w");
+  printf("%s %d\n", outside == NULL && errno == EROFS ? "EROFS" : "writable", inside != NULL);
+}
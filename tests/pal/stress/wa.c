@@ -0,0 +1,11 @@
+#include <stdio.h>
+
+int main(void) {
+  int a = 0;
+  scanf("%d", &a);
+  if (a <= 90) {
+    printf("%d", a + 1);
+  } else {
+    printf("%d", a);
+  }
+}